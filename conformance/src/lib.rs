@@ -0,0 +1,263 @@
+//! Boots todo-actix and todo-thruster on ports of their own and sends them the same requests, so
+//! the scenarios in `tests/` can check that both servers behave the same way.
+
+use std::fmt::{Debug, Write as _};
+use std::net::{TcpListener, TcpStream};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::Once;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The servers every scenario runs against, by package name.
+pub const SERVERS: [&str; 2] = ["todo-actix", "todo-thruster"];
+
+/// How long a server gets to start accepting connections.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A running server, with its todos in memory. It's killed when this is dropped.
+pub struct Server {
+    pub name: &'static str,
+    base: String,
+    agent: ureq::Agent,
+    child: Child,
+}
+
+impl Server {
+    /// Starts one of the servers on a free port and waits until it's accepting connections.
+    pub fn start(name: &'static str) -> Server {
+        build_servers();
+        let address = format!("127.0.0.1:{}", free_port());
+        let child = Command::new(target_dir().join(name))
+            .current_dir(workspace_root().join(name))
+            .env("TODO_LISTEN", &address)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap_or_else(|e| panic!("couldn't start {}: {}", name, e));
+        let mut server = Server {
+            name,
+            base: format!("http://{}", address),
+            // Redirects are part of what's being compared, so they aren't followed.
+            agent: ureq::AgentBuilder::new()
+                .redirects(0)
+                .timeout(Duration::from_secs(10))
+                .build(),
+            child,
+        };
+        server.wait_until_listening(&address);
+        server
+    }
+
+    fn wait_until_listening(&mut self, address: &str) {
+        let started = Instant::now();
+        while TcpStream::connect(address).is_err() {
+            if let Ok(Some(status)) = self.child.try_wait() {
+                panic!("{} exited before it was listening: {}", self.name, status);
+            }
+            if started.elapsed() > STARTUP_TIMEOUT {
+                panic!(
+                    "{} wasn't listening on {} after {:?}",
+                    self.name, address, STARTUP_TIMEOUT
+                );
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    pub fn get(&self, path: &str) -> Reply {
+        self.send("GET", path, &[], None)
+    }
+
+    pub fn post(&self, path: &str) -> Reply {
+        self.send("POST", path, &[], None)
+    }
+
+    pub fn post_form(&self, path: &str, body: impl AsRef<[u8]>) -> Reply {
+        self.send(
+            "POST",
+            path,
+            &[("Content-Type", "application/x-www-form-urlencoded")],
+            Some(body.as_ref()),
+        )
+    }
+
+    pub fn send(
+        &self,
+        method: &str,
+        path: &str,
+        headers: &[(&str, &str)],
+        body: Option<&[u8]>,
+    ) -> Reply {
+        let mut request = self
+            .agent
+            .request(method, &format!("{}{}", self.base, path));
+        for (name, value) in headers {
+            request = request.set(name, value);
+        }
+        let result = match body {
+            Some(body) => request.send_bytes(body),
+            None => request.call(),
+        };
+        self.reply(method, path, result)
+    }
+
+    /// Posts `body` without saying how long it is, so it goes chunked.
+    pub fn post_chunked(&self, path: &str, content_type: &str, body: &[u8]) -> Reply {
+        let result = self
+            .agent
+            .post(&format!("{}{}", self.base, path))
+            .set("Content-Type", content_type)
+            .send(body);
+        self.reply("POST", path, result)
+    }
+
+    fn reply(
+        &self,
+        method: &str,
+        path: &str,
+        result: Result<ureq::Response, ureq::Error>,
+    ) -> Reply {
+        let response = match result {
+            Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+            Err(e) => panic!("{}: {} {} failed: {}", self.name, method, path, e),
+        };
+        Reply {
+            status: response.status(),
+            content_type: response.content_type().to_owned(),
+            location: response.header("Location").map(str::to_owned),
+            etag: response.header("ETag").map(str::to_owned),
+            body: response
+                .into_string()
+                .unwrap_or_else(|e| panic!("{}: {} {} body: {}", self.name, method, path, e)),
+        }
+    }
+
+    /// Adds a todo through the HTML form and returns its id. The title goes into the form as it
+    /// is, so it shouldn't need encoding.
+    pub fn create(&self, title: &str, startable: &str, due: &str) -> String {
+        let body = format!("title={}&start-date={}&due-date={}", title, startable, due);
+        let reply = self.post_form("/new", body);
+        assert_eq!(
+            reply.status, 302,
+            "{}: creating {:?}: {:?}",
+            self.name, title, reply
+        );
+        let todos: serde_json::Value = serde_json::from_str(&self.get("/api/v1/todos").body)
+            .unwrap_or_else(|e| panic!("{}: listing todos: {}", self.name, e));
+        todos
+            .as_array()
+            .and_then(|todos| todos.iter().find(|todo| todo["title"] == title))
+            .and_then(|todo| todo["id"].as_str())
+            .unwrap_or_else(|| panic!("{}: {:?} wasn't in {}", self.name, title, todos))
+            .to_owned()
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// The parts of a response that scenarios look at.
+#[derive(Debug)]
+pub struct Reply {
+    pub status: u16,
+    /// Without any parameters, such as `charset`.
+    pub content_type: String,
+    pub location: Option<String>,
+    pub etag: Option<String>,
+    pub body: String,
+}
+
+impl Reply {
+    /// The titles of the todos listed on a page, in order.
+    pub fn titles(&self) -> Vec<String> {
+        const OPEN: &str = "<span class=\"todo-title\">";
+        self.body
+            .split(OPEN)
+            .skip(1)
+            .filter_map(|rest| rest.split("</span>").next())
+            .map(str::to_owned)
+            .collect()
+    }
+}
+
+/// Runs `scenario` against every server, each freshly started, then fails with a report of what
+/// each one did unless they all passed and agreed. A scenario returns whatever it saw that should
+/// be the same everywhere, and asserts on whatever should be true everywhere.
+pub fn conform<T, F>(scenario: F)
+where
+    T: PartialEq + Debug,
+    F: Fn(&Server) -> T,
+{
+    let outcomes: Vec<_> = SERVERS
+        .iter()
+        .map(|name| {
+            let outcome = panic::catch_unwind(AssertUnwindSafe(|| scenario(&Server::start(name))));
+            (name, outcome)
+        })
+        .collect();
+
+    let mut report = String::new();
+    for (name, outcome) in &outcomes {
+        match outcome {
+            Ok(observed) => writeln!(report, "{}: {:?}", name, observed),
+            Err(_) => writeln!(report, "{}: failed; its panic is above", name),
+        }
+        .unwrap();
+    }
+    let first = outcomes
+        .first()
+        .and_then(|(_, outcome)| outcome.as_ref().ok());
+    let agreed = outcomes
+        .iter()
+        .all(|(_, outcome)| outcome.is_ok() && outcome.as_ref().ok() == first);
+    assert!(agreed, "the servers didn't behave the same:\n{}", report);
+}
+
+/// A port nothing is listening on right now. Something else could take it before the server does,
+/// but on a test machine that's unlikely enough.
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .map(|address| address.port())
+        .expect("no free port")
+}
+
+fn workspace_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .expect("conformance is inside the workspace")
+        .to_owned()
+}
+
+/// `target/debug` (or `release`), worked out from where this test binary is: `target/*/deps`.
+fn target_dir() -> PathBuf {
+    let exe = std::env::current_exe().expect("no path to the test binary");
+    exe.parent()
+        .and_then(Path::parent)
+        .expect("test binary isn't in target/*/deps")
+        .to_owned()
+}
+
+/// Cargo only builds the binaries of the package under test, so the servers are built here, once
+/// per test run, with the same profile.
+fn build_servers() {
+    static BUILD: Once = Once::new();
+    BUILD.call_once(|| {
+        let mut cargo = Command::new(env!("CARGO"));
+        cargo.current_dir(workspace_root()).arg("build");
+        for name in SERVERS.iter() {
+            cargo.args(["-p", name]);
+        }
+        if target_dir().ends_with("release") {
+            cargo.arg("--release");
+        }
+        let status = cargo.status().expect("couldn't run cargo");
+        assert!(status.success(), "building the servers failed");
+    });
+}
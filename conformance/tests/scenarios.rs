@@ -0,0 +1,287 @@
+//! The same scenarios, run against both servers. Each returns what it saw that the servers should
+//! agree on; `conform` reports it side by side if they don't.
+
+use conformance::{conform, Server};
+use std::fs;
+use std::path::Path;
+
+const UNKNOWN_ID: &str = "00000000-0000-4000-8000-000000000000";
+
+fn todo(server: &Server, id: &str) -> serde_json::Value {
+    let reply = server.get(&format!("/api/v1/todos/{}", id));
+    assert_eq!(reply.status, 200, "{}: {:?}", server.name, reply);
+    serde_json::from_str(&reply.body).unwrap()
+}
+
+#[test]
+fn create_decodes_the_form_and_lists_the_todo() {
+    conform(|server| {
+        let created = server.post_form(
+            "/new",
+            concat!(
+                "title=dingbats+but+with+%25SPECIAL%25CHARACTERS%21%25",
+                "&start-date=1970-01-01&due-date=9999-12-31",
+            ),
+        );
+        assert_eq!(created.status, 302, "{}: {:?}", server.name, created);
+        assert_eq!(created.location.as_deref(), Some("/"), "{}", server.name);
+
+        let index = server.get("/");
+        assert_eq!(index.status, 200, "{}", server.name);
+        assert_eq!(index.content_type, "text/html", "{}", server.name);
+        assert_eq!(
+            index.titles(),
+            vec!["dingbats but with %SPECIAL%CHARACTERS!%"],
+            "{}",
+            server.name
+        );
+        (created.status, created.location, index.titles())
+    });
+}
+
+#[test]
+fn edit_checks_the_revision() {
+    conform(|server| {
+        let id = server.create("pasta", "2021-08-02", "2021-08-04");
+        let form = server.get(&format!("/edit/{}", id));
+        assert_eq!(form.status, 200, "{}", server.name);
+        assert_eq!(form.etag.as_deref(), Some("\"1\""), "{}", server.name);
+
+        let path = format!("/edit/{}", id);
+        let edited = server.post_form(
+            &path,
+            "title=linguine&start-date=2021-08-02&due-date=2021-08-05&revision=1",
+        );
+        assert_eq!(edited.status, 302, "{}: {:?}", server.name, edited);
+        let stale = server.post_form(
+            &path,
+            "title=penne&start-date=2021-08-02&due-date=2021-08-05&revision=1",
+        );
+        assert_eq!(stale.status, 409, "{}", server.name);
+        let missing = server.post_form(
+            &path,
+            "title=penne&start-date=2021-08-02&due-date=2021-08-05",
+        );
+        assert_eq!(missing.status, 428, "{}", server.name);
+        let malformed = server.post_form(
+            &path,
+            "title=penne&start-date=2021-08-02&due-date=2021-08-05&revision=abc",
+        );
+        assert_eq!(malformed.status, 400, "{}", server.name);
+
+        let stored = todo(server, &id);
+        assert_eq!(stored["title"], "linguine", "{}", server.name);
+        assert_eq!(stored["revision"], 2, "{}", server.name);
+        (
+            form.etag,
+            edited.status,
+            stale.status,
+            missing.status,
+            malformed.status,
+            stored["title"].clone(),
+        )
+    });
+}
+
+#[test]
+fn api_if_match_takes_a_star_but_not_a_malformed_etag() {
+    conform(|server| {
+        let id = server.create("pasta", "2021-08-02", "2021-08-04");
+        let path = format!("/api/v1/todos/{}", id);
+        let patch = |if_match: &str, title: &str| {
+            let body = format!("{{\"title\": \"{}\"}}", title);
+            server.send(
+                "PATCH",
+                &path,
+                &[("Content-Type", "application/json"), ("If-Match", if_match)],
+                Some(body.as_bytes()),
+            )
+        };
+        let any = patch("*", "linguine");
+        assert_eq!(any.status, 200, "{}: {:?}", server.name, any);
+        assert_eq!(any.etag.as_deref(), Some("\"2\""), "{}", server.name);
+        let malformed = patch("\"two\"", "penne");
+        assert_eq!(malformed.status, 400, "{}: {:?}", server.name, malformed);
+        assert_eq!(todo(server, &id)["title"], "linguine", "{}", server.name);
+        (any.status, any.etag, malformed.status, malformed.body)
+    });
+}
+
+#[test]
+fn complete_toggles() {
+    conform(|server| {
+        let id = server.create("laundry", "2021-08-02", "2021-08-04");
+        let path = format!("/complete/{}", id);
+        let first = server.post(&path);
+        assert_eq!(first.status, 200, "{}", server.name);
+        assert_eq!(first.body, "{}", "{}", server.name);
+        assert_eq!(todo(server, &id)["complete"], true, "{}", server.name);
+        let second = server.post(&path);
+        assert_eq!(second.status, 200, "{}", server.name);
+        assert_eq!(todo(server, &id)["complete"], false, "{}", server.name);
+        (first.status, first.content_type, first.body, second.status)
+    });
+}
+
+#[test]
+fn bad_and_unknown_ids_are_404() {
+    conform(|server| {
+        let form = "title=x&start-date=2021-08-02&due-date=2021-08-04&revision=1";
+        let mut statuses = Vec::new();
+        for id in &["not-a-uuid", UNKNOWN_ID] {
+            statuses.push(server.get(&format!("/edit/{}", id)).status);
+            statuses.push(server.post_form(&format!("/edit/{}", id), form).status);
+            for action in &["complete", "delete", "archive", "unarchive"] {
+                statuses.push(server.post(&format!("/{}/{}", action, id)).status);
+            }
+            statuses.push(server.get(&format!("/api/v1/todos/{}", id)).status);
+        }
+        assert!(
+            statuses.iter().all(|&status| status == 404),
+            "{}: {:?}",
+            server.name,
+            statuses
+        );
+        statuses
+    });
+}
+
+#[test]
+fn unknown_paths_are_404() {
+    conform(|server| {
+        let statuses = vec![
+            server.get("/nowhere").status,
+            server.get("/index.htm").status,
+        ];
+        assert!(
+            statuses.iter().all(|&status| status == 404),
+            "{}: {:?}",
+            server.name,
+            statuses
+        );
+        statuses
+    });
+}
+
+#[test]
+fn static_files_are_served() {
+    conform(|server| {
+        let mut seen = Vec::new();
+        for (file, content_type) in &[
+            ("site.css", "text/css"),
+            ("site.js", "application/javascript"),
+        ] {
+            let reply = server.get(&format!("/static/{}", file));
+            let on_disk = Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("..")
+                .join(server.name)
+                .join("static")
+                .join(file);
+            assert_eq!(reply.status, 200, "{}: {}", server.name, file);
+            assert_eq!(
+                reply.body,
+                fs::read_to_string(on_disk).unwrap(),
+                "{}: {}",
+                server.name,
+                file
+            );
+            assert_eq!(
+                &reply.content_type, content_type,
+                "{}: {}",
+                server.name, file
+            );
+            seen.push((reply.status, reply.content_type));
+        }
+        let missing = server.get("/static/nothing.css");
+        assert_eq!(missing.status, 404, "{}", server.name);
+        seen.push((missing.status, String::new()));
+        seen
+    });
+}
+
+#[test]
+fn malformed_forms_are_rejected() {
+    conform(|server| {
+        let cases: Vec<(&str, Vec<u8>, u16)> = vec![
+            ("empty", b"".to_vec(), 400),
+            ("no dates", b"title=x".to_vec(), 400),
+            (
+                "bad date",
+                b"title=x&start-date=yesterday&due-date=2021-08-04".to_vec(),
+                400,
+            ),
+            (
+                "due first",
+                b"title=x&start-date=2021-08-04&due-date=2021-08-02".to_vec(),
+                400,
+            ),
+            (
+                "long title",
+                format!(
+                    "title={}&start-date=2021-08-02&due-date=2021-08-04",
+                    "x".repeat(201)
+                )
+                .into_bytes(),
+                400,
+            ),
+            (
+                "repeated",
+                b"title=x&title=y&start-date=2021-08-02&due-date=2021-08-04".to_vec(),
+                400,
+            ),
+            (
+                "bad utf-8 escape",
+                b"title=%ff&start-date=2021-08-02&due-date=2021-08-04".to_vec(),
+                400,
+            ),
+            (
+                "raw bad utf-8",
+                b"title=\xff&start-date=2021-08-02&due-date=2021-08-04".to_vec(),
+                400,
+            ),
+            ("too many fields", "a=1&".repeat(100).into_bytes(), 413),
+            (
+                "too big",
+                format!("title={}", "x".repeat(70_000)).into_bytes(),
+                413,
+            ),
+        ];
+        let mut statuses = Vec::new();
+        for (name, body, expected) in cases {
+            let reply = server.post_form("/new", body);
+            assert_eq!(
+                reply.status, expected,
+                "{}: {}: {:?}",
+                server.name, name, reply
+            );
+            statuses.push((name, reply.status));
+        }
+        let index = server.get("/");
+        assert!(
+            index.titles().is_empty(),
+            "{}: {:?}",
+            server.name,
+            index.titles()
+        );
+        statuses
+    });
+}
+
+#[test]
+fn oversized_bodies_are_413_even_without_a_length() {
+    conform(|server| {
+        let form = format!("title={}", "x".repeat(70_000));
+        let json = format!("{{\"title\": \"{}\"}}", "x".repeat(40_000));
+        let cases = [
+            ("/new", "application/x-www-form-urlencoded", form),
+            ("/api/v1/todos", "application/json", json),
+        ];
+        let mut statuses = Vec::new();
+        for (path, content_type, body) in cases.iter() {
+            let reply = server.post_chunked(path, content_type, body.as_bytes());
+            assert_eq!(reply.status, 413, "{}: {}: {:?}", server.name, path, reply);
+            statuses.push((*path, reply.status));
+        }
+        statuses
+    });
+}
@@ -1,5 +1,45 @@
-use std::fmt;
 use std::env;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+mod replay;
+mod report;
+mod request;
+mod scenario;
+
+use replay::Replay;
+use report::Tally;
+use scenario::{Pool, Rng, Scenario};
+
+const USAGE: &str = "\
+usage: load-test [options] url
+
+    --concurrency N     how many threads send requests at once (default 1)
+    --duration SECONDS  stop after this long
+    --requests N        stop after this many requests in all
+    --rate N            send N requests a second, on schedule, instead of each thread sending
+                        its next request as soon as the last one's done
+    --scenario FILE     do what FILE says to the server at url, instead of getting url itself
+    --replay PATH       send the requests written down in PATH to the server at url, in turn;
+                        can be given more than once
+
+With neither --duration nor --requests, it runs for 10 seconds. With both, it stops at
+whichever comes first.
+
+With --rate, each request's latency is counted from when it was meant to be sent, so a server
+that falls behind is charged for the requests it kept waiting too. There have to be enough
+threads to keep up with the rate, or they'll fall behind as well.
+
+A scenario is a weighted mix of entries, each a request or a few in a row, that can capture
+values from their responses for later requests to use. In a scenario, --requests and --rate
+count entries rather than requests. See load-test/scenarios for some.
+
+PATH is a raw HTTP request, like those in fuzz-requests, a directory of them, or a .jsonl file
+with one request a line, like load-test/requests.jsonl. {{uuid}} and {{date}} in a request are
+filled in with a random UUID and today's date each time it's sent.";
 
 struct UsageError {
     message: String,
@@ -11,26 +51,282 @@ impl fmt::Debug for UsageError {
     }
 }
 
+impl UsageError {
+    fn new(message: impl Into<String>) -> UsageError {
+        UsageError {
+            message: message.into(),
+        }
+    }
+}
+
+struct Options {
+    url: String,
+    concurrency: usize,
+    duration: Option<Duration>,
+    requests: Option<u64>,
+    rate: Option<u64>,
+    scenario: Option<PathBuf>,
+    replay: Vec<PathBuf>,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Options, UsageError> {
+        let mut url = None;
+        let mut concurrency = 1;
+        let mut duration = None;
+        let mut requests = None;
+        let mut rate = None;
+        let mut scenario = None;
+        let mut replay = Vec::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if !arg.starts_with("--") {
+                if url.replace(arg.clone()).is_some() {
+                    return Err(UsageError::new("only one url, please"));
+                }
+                continue;
+            }
+            let value = args
+                .next()
+                .ok_or_else(|| UsageError::new(format!("{} needs a value", arg)))?;
+            match arg.as_str() {
+                "--concurrency" => concurrency = positive(arg, value)?,
+                "--duration" => duration = Some(Duration::from_secs(positive(arg, value)?)),
+                "--requests" => requests = Some(positive(arg, value)?),
+                "--rate" => rate = Some(positive(arg, value)?),
+                "--scenario" => scenario = Some(PathBuf::from(value)),
+                "--replay" => replay.push(PathBuf::from(value)),
+                _ => return Err(UsageError::new(format!("unknown option {}", arg))),
+            }
+        }
+        let url = url.ok_or_else(|| UsageError::new("not enough arguments"))?;
+        if scenario.is_some() && !replay.is_empty() {
+            return Err(UsageError::new("a scenario or a replay, not both"));
+        }
+        if duration.is_none() && requests.is_none() {
+            duration = Some(Duration::from_secs(10));
+        }
+        Ok(Options {
+            url,
+            concurrency: concurrency as usize,
+            duration,
+            requests,
+            rate,
+            scenario,
+            replay,
+        })
+    }
+}
+
+fn positive(flag: &str, value: &str) -> Result<u64, UsageError> {
+    match value.parse() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(UsageError::new(format!(
+            "{} has to be a positive number, not {:?}",
+            flag, value
+        ))),
+    }
+}
+
+/// What each thread does over and over.
+enum Work {
+    Get(String),
+    Scenario {
+        base: String,
+        scenario: Scenario,
+        pool: Pool,
+    },
+    Replay {
+        base: String,
+        replay: Replay,
+    },
+}
+
+/// What the threads share: when to stop, how fast to go, and how many requests they've started
+/// between them.
+struct Budget {
+    start: Instant,
+    deadline: Option<Instant>,
+    limit: Option<u64>,
+    /// Requests a second, for an open loop.
+    rate: Option<u64>,
+    started: AtomicU64,
+}
+
+impl Budget {
+    /// Claims the next request and says when it's due: as soon as possible in a closed loop, or
+    /// its place in the schedule in an open one. Returns `None` once time or requests run out.
+    fn take(&self) -> Option<Instant> {
+        let n = self.started.fetch_add(1, Ordering::Relaxed);
+        if self.limit.is_some_and(|limit| n >= limit) {
+            return None;
+        }
+        let due = match self.rate {
+            Some(rate) => self.start + Duration::from_secs_f64(n as f64 / rate as f64),
+            None => Instant::now(),
+        };
+        if self.deadline.is_some_and(|deadline| due >= deadline) {
+            return None;
+        }
+        Some(due)
+    }
+
+    fn started(&self) -> u64 {
+        let started = self.started.load(Ordering::Relaxed);
+        self.limit.map_or(started, |limit| started.min(limit))
+    }
+}
+
 fn main() -> Result<(), UsageError> {
-    let args: Vec<String> = env::args().collect();
-    if args.len() < 2 {
-        println!("usage: load-test url");
-        return Err(UsageError { message: "not enough arguments".to_string() });
-    } else {
-        thread_loop(&args[1].to_owned());
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = match Options::parse(&args) {
+        Ok(options) => options,
+        Err(e) => {
+            println!("{}", USAGE);
+            return Err(e);
+        }
+    };
+
+    let work = match &options.scenario {
+        Some(path) => Work::Scenario {
+            base: options.url.trim_end_matches('/').to_owned(),
+            scenario: Scenario::load(path).map_err(UsageError::new)?,
+            pool: Pool::default(),
+        },
+        None if !options.replay.is_empty() => Work::Replay {
+            base: options.url.trim_end_matches('/').to_owned(),
+            replay: Replay::load(&options.replay).map_err(UsageError::new)?,
+        },
+        None => Work::Get(options.url.clone()),
+    };
+
+    // What the budget counts.
+    let unit = match work {
+        Work::Get(_) | Work::Replay { .. } => "requests",
+        Work::Scenario { .. } => "entries",
+    };
+
+    let start = Instant::now();
+    let budget = Budget {
+        start,
+        deadline: options.duration.map(|duration| start + duration),
+        limit: options.requests,
+        rate: options.rate,
+        started: AtomicU64::new(0),
+    };
+    let tally = thread::scope(|scope| {
+        let threads: Vec<_> = (0..options.concurrency)
+            .map(|_| scope.spawn(|| thread_loop(&work, &budget)))
+            .collect();
+        let mut next_report = start + Duration::from_secs(1);
+        while !threads.iter().all(|thread| thread.is_finished()) {
+            thread::sleep(Duration::from_millis(100));
+            if Instant::now() >= next_report {
+                println!("made {} {} so far", budget.started(), unit);
+                next_report += Duration::from_secs(1);
+            }
+        }
+        let mut tally = Tally::new();
+        for thread in threads {
+            tally.merge(thread.join().unwrap());
+        }
+        tally
+    });
+    let elapsed = start.elapsed();
+
+    println!(
+        "made {} requests in {:.2}s with {} threads: {:.1} requests/s",
+        tally.requests(),
+        elapsed.as_secs_f64(),
+        options.concurrency,
+        tally.requests() as f64 / elapsed.as_secs_f64()
+    );
+    if let Some(rate) = options.rate {
+        println!("aiming for {} {}/s", rate, unit);
     }
+    tally.print();
     Ok(())
 }
 
-fn thread_loop(hostname: &str) {
-    let mut thread_local_request_counter = 0;
-    loop {
-        // Intentionally don't use an Agent; make as many separate
-        // connections as possible.
-        let _ = ureq::get(hostname).call();
-        thread_local_request_counter += 1;
-        if thread_local_request_counter % 100 == 0 {
-            println!("made {} requests so far", thread_local_request_counter);
+/// Sends requests until the budget runs out, and returns how they went.
+fn thread_loop(work: &Work, budget: &Budget) -> Tally {
+    let mut tally = Tally::new();
+    let mut rng = Rng::new();
+    while let Some(due) = budget.take() {
+        if let Some(wait) = due.checked_duration_since(Instant::now()) {
+            thread::sleep(wait);
+        }
+        match work {
+            Work::Get(url) => {
+                // Intentionally don't use an Agent; make as many separate
+                // connections as possible.
+                tally.record(url, ureq::get(url).call(), due);
+            }
+            Work::Scenario {
+                base,
+                scenario,
+                pool,
+            } => scenario.run(base, pool, &mut rng, &mut tally, due),
+            Work::Replay { base, replay } => replay.run(base, &mut tally, due),
         }
     }
+    tally
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_budget(duration: Option<Duration>, limit: Option<u64>, rate: Option<u64>) -> Budget {
+        let start = Instant::now();
+        Budget {
+            start,
+            deadline: duration.map(|duration| start + duration),
+            limit,
+            rate,
+            started: AtomicU64::new(0),
+        }
+    }
+
+    #[test]
+    fn an_open_loop_schedules_each_request_at_n_over_rate() {
+        let budget = new_budget(None, Some(5), Some(4));
+        for n in 0..5 {
+            let due = budget.take().unwrap();
+            assert_eq!(due - budget.start, Duration::from_millis(250 * n));
+        }
+    }
+
+    #[test]
+    fn stops_at_the_limit() {
+        let budget = new_budget(None, Some(3), None);
+        assert!((0..3).all(|_| budget.take().is_some()));
+        assert_eq!(budget.take(), None);
+        assert_eq!(budget.take(), None);
+    }
+
+    #[test]
+    fn nothing_is_scheduled_at_or_after_the_deadline() {
+        // Two a second for a second is the requests due at 0 and 0.5s, but not the one at 1s.
+        let budget = new_budget(Some(Duration::from_secs(1)), None, Some(2));
+        let due: Vec<_> = std::iter::from_fn(|| budget.take()).collect();
+        assert_eq!(
+            due,
+            vec![budget.start, budget.start + Duration::from_millis(500)]
+        );
+    }
+
+    #[test]
+    fn started_never_counts_past_the_limit() {
+        let budget = new_budget(None, Some(2), None);
+        assert_eq!(budget.started(), 0);
+        while budget.take().is_some() {}
+        budget.take();
+        assert_eq!(budget.started(), 2);
+        assert!(budget.started.load(Ordering::Relaxed) > 2);
+
+        let unlimited = new_budget(None, None, Some(1000));
+        (0..7).for_each(|_| assert!(unlimited.take().is_some()));
+        assert_eq!(unlimited.started(), 7);
+    }
 }
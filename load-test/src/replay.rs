@@ -0,0 +1,266 @@
+//! Replaying requests written down ahead of time, such as the raw ones in `fuzz-requests/` that
+//! `fuzz.sh` feeds to radamsa, so benchmarks and fuzzing can share them.
+//!
+//! A file is read as one raw HTTP request, a directory as one for each file in it, and a `.jsonl`
+//! file as one a line, like:
+//!
+//! ```json
+//! {"method": "POST", "path": "/new", "headers": {"Content-Type": "application/x-www-form-urlencoded"}, "body": "title=x&start-date={{date}}&due-date={{date}}"}
+//! ```
+//!
+//! where only `path` is required. Anywhere in a request, `{{uuid}}` is replaced with a new random
+//! UUID and `{{date}}` with today's date, each time it's sent. They're replayed in order, over
+//! and over, against the url given on the command line rather than whatever host they name.
+
+use crate::report::Tally;
+use crate::request;
+use chrono::Utc;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+use uuid::Uuid;
+
+/// Headers that are about the connection the request was captured on, and that ureq works out
+/// for itself.
+const LEFT_OUT: &[&str] = &["Host", "Content-Length", "Connection", "Transfer-Encoding"];
+
+/// What can go in `{{...}}`.
+const VARIABLES: &[&str] = &["uuid", "date"];
+
+struct Template {
+    method: String,
+    path: String,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+/// A line of a `.jsonl` file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Line {
+    #[serde(default = "get")]
+    method: String,
+    path: String,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    #[serde(default)]
+    body: String,
+}
+
+fn get() -> String {
+    "GET".to_owned()
+}
+
+pub struct Replay {
+    templates: Vec<Template>,
+    /// Which template the next request uses, before wrapping around.
+    next: AtomicUsize,
+}
+
+impl Replay {
+    pub fn load(paths: &[impl AsRef<Path>]) -> Result<Replay, String> {
+        let mut templates = Vec::new();
+        for path in paths {
+            load_path(path.as_ref(), &mut templates)?;
+        }
+        if templates.is_empty() {
+            return Err("no requests to replay".to_owned());
+        }
+        Ok(Replay {
+            templates,
+            next: AtomicUsize::new(0),
+        })
+    }
+
+    /// Sends the next request to `base`, with its latency running from `due`.
+    pub fn run(&self, base: &str, tally: &mut Tally, due: Instant) {
+        let n = self.next.fetch_add(1, Ordering::Relaxed);
+        let template = &self.templates[n % self.templates.len()];
+
+        let filled = (|| {
+            let path = request::fill(&template.path, value)?;
+            let mut headers = Vec::with_capacity(template.headers.len());
+            for (name, header) in &template.headers {
+                headers.push((name.as_str(), request::fill(header, value)?));
+            }
+            let body = request::fill(&template.body, value)?;
+            Ok::<_, String>((path, headers, body))
+        })();
+        let (path, headers, body) = match filled {
+            Ok(filled) => filled,
+            Err(name) => {
+                tally.skip(format!("nothing to fill in {{{{{}}}}} with", name));
+                return;
+            }
+        };
+        let headers: Vec<_> = headers
+            .iter()
+            .map(|(name, value)| (*name, value.as_str()))
+            .collect();
+        let body = Some(body.as_str()).filter(|body| !body.is_empty());
+        let result = request::send(base, &template.method, &path, &headers, body);
+        let label = format!("{} {}", template.method, template.path);
+        tally.record(&label, result, due);
+    }
+}
+
+fn load_path(path: &Path, templates: &mut Vec<Template>) -> Result<(), String> {
+    let read_error = |e| format!("couldn't read {}: {}", path.display(), e);
+    if path.is_dir() {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(path).map_err(read_error)? {
+            let file = entry.map_err(read_error)?.path();
+            let hidden = file
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+            if file.is_file() && !hidden {
+                files.push(file);
+            }
+        }
+        files.sort();
+        for file in files {
+            load_path(&file, templates)?;
+        }
+        return Ok(());
+    }
+
+    let text = fs::read_to_string(path).map_err(read_error)?;
+    if path
+        .extension()
+        .is_some_and(|extension| extension == "jsonl")
+    {
+        let lines = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty());
+        for (number, line) in lines {
+            let where_ = || format!("{} line {}", path.display(), number + 1);
+            let line: Line =
+                serde_json::from_str(line).map_err(|e| format!("{}: {}", where_(), e))?;
+            let template = Template {
+                method: line.method,
+                path: line.path,
+                headers: line.headers.into_iter().collect(),
+                body: line.body,
+            };
+            templates.push(check(template).map_err(|e| format!("{}: {}", where_(), e))?);
+        }
+    } else {
+        let template = parse_raw(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+        templates.push(check(template).map_err(|e| format!("{}: {}", path.display(), e))?);
+    }
+    Ok(())
+}
+
+/// A request as it'd go over the wire, with either kind of line ending.
+fn parse_raw(text: &str) -> Result<Template, String> {
+    let separators = ["\r\n\r\n", "\n\n"].iter();
+    let end_of_head = separators
+        .filter_map(|separator| Some((text.find(separator)?, separator.len())))
+        .min();
+    let (head, body) = match end_of_head {
+        Some((end, length)) => (&text[..end], &text[end + length..]),
+        None => (text, ""),
+    };
+    let mut lines = head.lines();
+    let request_line = lines.next().unwrap_or("");
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = match (parts.next(), parts.next()) {
+        (Some(method), Some(path)) if path.starts_with('/') => (method, path),
+        _ => return Err(format!("{:?} isn't a request line", request_line)),
+    };
+
+    let mut headers = Vec::new();
+    let mut length = None;
+    for line in lines {
+        let (name, value) = line
+            .split_once(':')
+            .ok_or_else(|| format!("{:?} isn't a header", line))?;
+        let name = name.trim();
+        if name.eq_ignore_ascii_case("Content-Length") {
+            let value = value.trim();
+            length = Some(
+                value
+                    .parse::<usize>()
+                    .map_err(|_| format!("{:?} isn't a Content-Length", value))?,
+            );
+        }
+        if !LEFT_OUT
+            .iter()
+            .any(|left_out| left_out.eq_ignore_ascii_case(name))
+        {
+            headers.push((name.to_owned(), value.trim().to_owned()));
+        }
+    }
+    // Captured files tend to end in a newline that was never part of the request, so a body that
+    // says how long it is gets cut to that.
+    let body = match length {
+        Some(length) => body.get(..length).ok_or_else(|| {
+            format!(
+                "Content-Length is {} but the body is {} bytes",
+                length,
+                body.len()
+            )
+        })?,
+        None => body,
+    };
+    Ok(Template {
+        method: method.to_owned(),
+        path: path.to_owned(),
+        headers,
+        body: body.to_owned(),
+    })
+}
+
+/// Makes sure every `{{name}}` in the template is one that can be filled in.
+fn check(template: Template) -> Result<Template, String> {
+    let fixed = [&template.path, &template.body];
+    let headers = template.headers.iter().map(|(_, value)| value);
+    for text in fixed.iter().copied().chain(headers) {
+        if let Some(name) = request::names(text).find(|name| !VARIABLES.contains(name)) {
+            return Err(format!("there's nothing to fill in {{{{{}}}}} with", name));
+        }
+    }
+    Ok(template)
+}
+
+fn value(name: &str) -> Result<String, String> {
+    match name {
+        "uuid" => Ok(Uuid::new_v4().to_string()),
+        "date" => Ok(Utc::today().naive_utc().to_string()),
+        _ => Err(name.to_owned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_body_stops_at_its_content_length() {
+        let template = parse_raw(include_str!("../../fuzz-requests/post-new")).unwrap();
+        assert_eq!(
+            template.body,
+            "title=iatetoomuchpasta&start-date=2021-08-02&due-date=2021-08-04"
+        );
+        assert!(template
+            .headers
+            .iter()
+            .all(|(name, _)| !name.eq_ignore_ascii_case("Content-Length")));
+    }
+
+    #[test]
+    fn without_a_content_length_the_body_is_everything_after_the_head() {
+        let template = parse_raw("POST /new HTTP/1.1\r\n\r\ntitle=a\n").unwrap();
+        assert_eq!(template.body, "title=a\n");
+    }
+
+    #[test]
+    fn a_content_length_past_the_end_is_an_error() {
+        assert!(parse_raw("POST /new HTTP/1.1\nContent-Length: 10\n\ntitle=a").is_err());
+        assert!(parse_raw("POST /new HTTP/1.1\nContent-Length: ten\n\ntitle=a").is_err());
+    }
+}
@@ -0,0 +1,188 @@
+//! Keeping track of how requests went, and summing it all up at the end.
+
+use hdrhistogram::Histogram;
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::time::Instant;
+
+/// Latencies are recorded in microseconds, up to a minute, to three significant figures.
+const LONGEST: u64 = 60_000_000;
+
+fn histogram() -> Histogram<u64> {
+    Histogram::new_with_bounds(1, LONGEST, 3).expect("valid histogram bounds")
+}
+
+/// A whole response, for anything that wants to pick something out of it.
+pub struct Reply {
+    pub(crate) headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Reply {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        let mut headers = self.headers.iter();
+        let found = headers.find(|(header, _)| header.eq_ignore_ascii_case(name));
+        found.map(|(_, value)| value.as_str())
+    }
+}
+
+/// What one thread saw. Each thread keeps its own, and they're merged once they're all done.
+pub struct Tally {
+    latencies: Histogram<u64>,
+    /// The same latencies again, split up by what kind of request they were.
+    by_label: BTreeMap<String, Histogram<u64>>,
+    statuses: BTreeMap<u16, u64>,
+    /// Requests that never got a whole response, by what went wrong.
+    transport_errors: BTreeMap<String, u64>,
+    /// Requests that couldn't be made, by why not.
+    skipped: BTreeMap<String, u64>,
+}
+
+impl Tally {
+    pub fn new() -> Tally {
+        Tally {
+            latencies: histogram(),
+            by_label: BTreeMap::new(),
+            statuses: BTreeMap::new(),
+            transport_errors: BTreeMap::new(),
+            skipped: BTreeMap::new(),
+        }
+    }
+
+    /// Reads the whole response, if there is one, and records how it went under `label`. Its
+    /// latency runs from `start` to when the last of the body arrived.
+    pub fn record(
+        &mut self,
+        label: &str,
+        result: Result<ureq::Response, ureq::Error>,
+        start: Instant,
+    ) -> Option<Reply> {
+        let response = match result {
+            Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+            Err(e) => {
+                count(&mut self.transport_errors, e.kind().to_string());
+                return None;
+            }
+        };
+        let status = response.status();
+        let headers = response
+            .headers_names()
+            .into_iter()
+            .filter_map(|name| {
+                let value = response.header(&name)?.to_owned();
+                Some((name, value))
+            })
+            .collect();
+        let mut body = Vec::new();
+        if let Err(e) = response.into_reader().read_to_end(&mut body) {
+            count(
+                &mut self.transport_errors,
+                format!("reading the body: {}", e.kind()),
+            );
+            return None;
+        }
+
+        let micros = start.elapsed().as_micros().min(u128::from(LONGEST)) as u64;
+        self.latencies.saturating_record(micros.max(1));
+        self.by_label
+            .entry(label.to_owned())
+            .or_insert_with(histogram)
+            .saturating_record(micros.max(1));
+        *self.statuses.entry(status).or_default() += 1;
+        Some(Reply {
+            headers,
+            body: String::from_utf8_lossy(&body).into_owned(),
+        })
+    }
+
+    pub fn skip(&mut self, reason: String) {
+        count(&mut self.skipped, reason);
+    }
+
+    pub fn merge(&mut self, other: Tally) {
+        self.latencies
+            .add(&other.latencies)
+            .expect("histograms with the same bounds");
+        for (label, latencies) in other.by_label {
+            self.by_label
+                .entry(label)
+                .or_insert_with(histogram)
+                .add(&latencies)
+                .expect("histograms with the same bounds");
+        }
+        for (status, n) in other.statuses {
+            *self.statuses.entry(status).or_default() += n;
+        }
+        for (error, n) in other.transport_errors {
+            *self.transport_errors.entry(error).or_default() += n;
+        }
+        for (reason, n) in other.skipped {
+            *self.skipped.entry(reason).or_default() += n;
+        }
+    }
+
+    pub fn requests(&self) -> u64 {
+        self.statuses.values().sum::<u64>() + self.transport_errors.values().sum::<u64>()
+    }
+
+    /// Requests that got an error status or no response at all.
+    pub fn errors(&self) -> u64 {
+        let statuses = self.statuses.range(400..).map(|(_, n)| n);
+        statuses.sum::<u64>() + self.transport_errors.values().sum::<u64>()
+    }
+
+    pub fn print(&self) {
+        if !self.latencies.is_empty() {
+            println!();
+            println!(
+                "{:>9} {:>9} {:>9} {:>9} {:>9} {:>9}  latency of",
+                "p50", "p90", "p99", "p99.9", "max", "count"
+            );
+            print_latencies("everything", &self.latencies);
+            // With only one kind of request, it'd be the same line again.
+            if self.by_label.len() > 1 {
+                for (label, latencies) in &self.by_label {
+                    print_latencies(label, latencies);
+                }
+            }
+        }
+
+        println!();
+        for (status, n) in &self.statuses {
+            println!("status {}: {}", status, n);
+        }
+        for (error, n) in &self.transport_errors {
+            println!("no response ({}): {}", error, n);
+        }
+        for (reason, n) in &self.skipped {
+            println!("skipped ({}): {}", reason, n);
+        }
+        let errors = self.errors();
+        let requests = self.requests().max(1);
+        println!(
+            "errors: {} ({:.2}%)",
+            errors,
+            errors as f64 * 100.0 / requests as f64
+        );
+    }
+}
+
+fn count(counts: &mut BTreeMap<String, u64>, key: String) {
+    *counts.entry(key).or_default() += 1;
+}
+
+fn print_latencies(label: &str, latencies: &Histogram<u64>) {
+    for quantile in &[0.5, 0.9, 0.99, 0.999] {
+        print!("{:>9} ", millis(latencies.value_at_quantile(*quantile)));
+    }
+    println!(
+        "{:>9} {:>9}  {}",
+        millis(latencies.max()),
+        latencies.len(),
+        label
+    );
+}
+
+fn millis(micros: u64) -> String {
+    format!("{:.2}ms", micros as f64 / 1000.0)
+}
@@ -0,0 +1,52 @@
+//! What scenarios and replays have in common: requests written down with `{{name}}` in them, and
+//! sending each one once it's been filled in.
+
+/// `template` with every `{{name}}` filled in with `value(name)`, or the first error that gives.
+pub fn fill<E>(
+    template: &str,
+    mut value: impl FnMut(&str) -> Result<String, E>,
+) -> Result<String, E> {
+    let mut filled = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let end = match rest[start..].find("}}") {
+            Some(end) => start + end,
+            None => break,
+        };
+        filled.push_str(&rest[..start]);
+        filled.push_str(&value(rest[start + 2..end].trim())?);
+        rest = &rest[end + 2..];
+    }
+    filled.push_str(rest);
+    Ok(filled)
+}
+
+/// The name inside each `{{name}}` in `text`.
+pub fn names(text: &str) -> impl Iterator<Item = &str> {
+    text.split("{{")
+        .skip(1)
+        .filter_map(|rest| Some(rest.split_once("}}")?.0.trim()))
+}
+
+/// Sends `method` to `base` followed by `path`, with `body` if there is one.
+// ureq's error is what it is, and it goes straight to `Tally::record`.
+#[allow(clippy::result_large_err)]
+pub fn send(
+    base: &str,
+    method: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+    body: Option<&str>,
+) -> Result<ureq::Response, ureq::Error> {
+    // Intentionally don't reuse an Agent; make as many separate connections as possible.
+    // Redirects are left alone, so each request is timed on its own.
+    let agent = ureq::builder().redirects(0).build();
+    let mut request = agent.request(method, &format!("{}{}", base, path));
+    for (name, value) in headers {
+        request = request.set(name, value);
+    }
+    match body {
+        Some(body) => request.send_string(body),
+        None => request.call(),
+    }
+}
@@ -0,0 +1,406 @@
+//! Scenario files: a weighted mix of things to do instead of getting one url over and over.
+//!
+//! A scenario is a TOML file with any number of entries like this one, which looks at a todo's
+//! edit form and then saves it:
+//!
+//! ```toml
+//! [[entry]]
+//! name = "edit"
+//! weight = 2
+//!
+//! [[entry.step]]
+//! path = "/edit/{{id}}"
+//! capture = [{ name = "revision", header = "ETag", after = '"', before = '"' }]
+//!
+//! [[entry.step]]
+//! method = "POST"
+//! path = "/edit/{{id}}"
+//! form = "title=edited&start-date=2021-08-02&due-date=2030-01-01&revision={{revision}}"
+//! ```
+//!
+//! Each time, one entry is picked at random, more often the higher its weight, and its steps
+//! are sent in order. A `{{name}}` is filled in with a value captured earlier in the same entry,
+//! or failing that, one captured by any entry before it. An entry that needs a value nobody's
+//! captured yet is skipped.
+
+use crate::report::{Reply, Tally};
+use crate::request;
+use serde::Deserialize;
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// How many values are kept under each name to choose from. The oldest are dropped first.
+const POOL_SIZE: usize = 1000;
+
+const FORM: &str = "application/x-www-form-urlencoded";
+const JSON: &str = "application/json";
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    #[serde(rename = "entry")]
+    entries: Vec<Entry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Entry {
+    name: String,
+    /// How often it's picked, compared to the other entries.
+    #[serde(default = "one")]
+    weight: u64,
+    #[serde(rename = "step")]
+    steps: Vec<Step>,
+}
+
+fn one() -> u64 {
+    1
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Step {
+    #[serde(default = "get")]
+    method: String,
+    /// Added to the end of the base url.
+    path: String,
+    /// A urlencoded form to send.
+    form: Option<String>,
+    /// JSON to send.
+    json: Option<String>,
+    #[serde(default)]
+    capture: Vec<Capture>,
+}
+
+fn get() -> String {
+    "GET".to_owned()
+}
+
+/// Picks values out of a response: everything between `after` and `before`, in the body or
+/// in the named header. Without `before`, it's everything after the first `after`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Capture {
+    name: String,
+    header: Option<String>,
+    #[serde(default)]
+    after: String,
+    before: Option<String>,
+}
+
+impl Capture {
+    fn find(&self, reply: &Reply) -> Vec<String> {
+        let mut rest = match &self.header {
+            Some(header) => match reply.header(header) {
+                Some(value) => value,
+                None => return Vec::new(),
+            },
+            None => &reply.body,
+        };
+        let mut found = Vec::new();
+        while let Some(start) = rest.find(&self.after) {
+            rest = &rest[start + self.after.len()..];
+            let before = match &self.before {
+                Some(before) => before,
+                None => {
+                    found.push(rest.to_owned());
+                    break;
+                }
+            };
+            match rest.find(before.as_str()) {
+                Some(end) => {
+                    found.push(rest[..end].to_owned());
+                    rest = &rest[end + before.len()..];
+                }
+                None => break,
+            }
+        }
+        found
+    }
+}
+
+impl Scenario {
+    pub fn load(path: &Path) -> Result<Scenario, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("couldn't read {}: {}", path.display(), e))?;
+        let scenario: Scenario = toml::from_str(&text)
+            .map_err(|e| format!("couldn't parse {}: {}", path.display(), e))?;
+        if scenario.entries.iter().all(|entry| entry.weight == 0) {
+            return Err(format!("{} has nothing to do", path.display()));
+        }
+        for entry in &scenario.entries {
+            if entry.steps.is_empty() {
+                return Err(format!("{} has no steps", entry.name));
+            }
+            for step in &entry.steps {
+                if step.form.is_some() && step.json.is_some() {
+                    return Err(format!("{} sends both a form and JSON", entry.name));
+                }
+                if step.capture.iter().any(|c| c.before.as_deref() == Some("")) {
+                    return Err(format!("{} captures up to nothing", entry.name));
+                }
+            }
+        }
+        Ok(scenario)
+    }
+
+    fn pick(&self, rng: &mut Rng) -> &Entry {
+        let total: u64 = self.entries.iter().map(|entry| entry.weight).sum();
+        let mut n = rng.below(total);
+        for entry in &self.entries {
+            if n < entry.weight {
+                return entry;
+            }
+            n -= entry.weight;
+        }
+        unreachable!("picked past the last entry")
+    }
+
+    /// Picks an entry and sends its steps to `base`, one after the other. The first one's latency
+    /// runs from `due`, and each of the others' from when it's sent.
+    pub fn run(&self, base: &str, pool: &Pool, rng: &mut Rng, tally: &mut Tally, due: Instant) {
+        let entry = self.pick(rng);
+        let mut captured = HashMap::new();
+        let mut start = due;
+        for step in &entry.steps {
+            let mut fill = |template: &str| fill(template, &mut captured, pool, rng);
+            let filled = (|| {
+                let path = fill(&step.path)?;
+                let form = step.form.as_deref().map(&mut fill).transpose()?;
+                let json = step.json.as_deref().map(&mut fill).transpose()?;
+                Ok::<_, String>((path, form, json))
+            })();
+            let (path, form, json) = match filled {
+                Ok(filled) => filled,
+                Err(name) => {
+                    tally.skip(format!("{} before any {{{{{}}}}}", entry.name, name));
+                    return;
+                }
+            };
+
+            let (headers, body): (&[_], _) = match (&form, &json) {
+                (Some(form), _) => (&[("Content-Type", FORM)], Some(form)),
+                (None, Some(json)) => (&[("Content-Type", JSON)], Some(json)),
+                (None, None) => (&[], None),
+            };
+            let result =
+                request::send(base, &step.method, &path, headers, body.map(String::as_str));
+            let label = format!("{} {}", step.method, step.path);
+            let reply = match tally.record(&label, result, start) {
+                Some(reply) => reply,
+                None => return,
+            };
+            for capture in &step.capture {
+                let found = capture.find(&reply);
+                if let Some(first) = found.first() {
+                    captured.insert(capture.name.clone(), first.clone());
+                }
+                pool.add(&capture.name, found);
+            }
+            start = Instant::now();
+        }
+    }
+}
+
+/// `template` with every `{{name}}` filled in: with what's in `captured`, or else with any value
+/// in the pool, which is then kept in `captured` so the rest of the entry uses it too. Gives
+/// back the first name that has no value.
+fn fill(
+    template: &str,
+    captured: &mut HashMap<String, String>,
+    pool: &Pool,
+    rng: &mut Rng,
+) -> Result<String, String> {
+    request::fill(template, |name| {
+        if let Some(value) = captured.get(name) {
+            return Ok(value.clone());
+        }
+        let value = pool.pick(name, rng).ok_or_else(|| name.to_owned())?;
+        captured.insert(name.to_owned(), value.clone());
+        Ok(value)
+    })
+}
+
+/// Every value captured so far, by name, shared between the threads.
+#[derive(Default)]
+pub struct Pool {
+    values: Mutex<HashMap<String, VecDeque<String>>>,
+}
+
+impl Pool {
+    fn add(&self, name: &str, found: Vec<String>) {
+        if found.is_empty() {
+            return;
+        }
+        let mut values = self.values.lock().unwrap();
+        let values = values.entry(name.to_owned()).or_default();
+        for value in found {
+            if values.len() == POOL_SIZE {
+                values.pop_front();
+            }
+            values.push_back(value);
+        }
+    }
+
+    fn pick(&self, name: &str, rng: &mut Rng) -> Option<String> {
+        let values = self.values.lock().unwrap();
+        let values = values.get(name)?;
+        values.get(rng.below(values.len() as u64) as usize).cloned()
+    }
+}
+
+/// Just enough randomness to pick entries and values with (xorshift64*).
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new() -> Rng {
+        Rng(RandomState::new().build_hasher().finish() | 1)
+    }
+
+    /// A number from 0 up to but not including `n`.
+    fn below(&mut self, n: u64) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d) % n
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn capture(after: &str, before: Option<&str>, header: Option<&str>) -> Capture {
+        Capture {
+            name: "id".to_owned(),
+            header: header.map(str::to_owned),
+            after: after.to_owned(),
+            before: before.map(str::to_owned),
+        }
+    }
+
+    fn reply(headers: &[(&str, &str)], body: &str) -> Reply {
+        Reply {
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            body: body.to_owned(),
+        }
+    }
+
+    /// Loads `text` as a scenario file, from a file of the test's own that's gone afterwards.
+    fn load(name: &str, text: &str) -> Result<Scenario, String> {
+        let path = std::env::temp_dir()
+            .join(format!("scenario-{}-{}.toml", name, std::process::id()));
+        fs::write(&path, text).unwrap();
+        let scenario = Scenario::load(&path);
+        fs::remove_file(&path).unwrap();
+        scenario
+    }
+
+    /// Why loading `text` as a scenario file fails.
+    fn rejection(name: &str, text: &str) -> String {
+        match load(name, text) {
+            Ok(_) => panic!("{} loaded", name),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn finds_everything_between_after_and_before() {
+        let body = r#"<li id="a">x</li><li id="b">y</li><li id="c"#;
+        let found = capture(r#"<li id=""#, Some("\""), None).find(&reply(&[], body));
+        // The last one never ends, so it isn't a match.
+        assert_eq!(found, ["a", "b"]);
+    }
+
+    #[test]
+    fn without_before_takes_the_rest_after_the_first_match() {
+        let found = capture("id=", None, None).find(&reply(&[], "id=1&id=2"));
+        assert_eq!(found, ["1&id=2"]);
+        assert!(capture("nope", None, None).find(&reply(&[], "id=1")).is_empty());
+    }
+
+    #[test]
+    fn looks_in_a_header_instead_of_the_body_when_named() {
+        let reply = reply(&[("ETag", "\"7\"")], "\"3\"");
+        let etag = capture("\"", Some("\""), Some("etag")).find(&reply);
+        assert_eq!(etag, ["7"]);
+        let body = capture("\"", Some("\""), None).find(&reply);
+        assert_eq!(body, ["3"]);
+        // Even a capture that matches anything finds nothing in a header that isn't there.
+        assert!(capture("", None, Some("Location")).find(&reply).is_empty());
+    }
+
+    #[test]
+    fn never_picks_an_entry_with_no_weight() {
+        let scenario = load(
+            "weights",
+            r#"
+            [[entry]]
+            name = "never"
+            weight = 0
+            step = [{ path = "/never" }]
+
+            [[entry]]
+            name = "always"
+            weight = 3
+            step = [{ path = "/" }]
+
+            [[entry]]
+            name = "also never"
+            weight = 0
+            step = [{ path = "/never" }]
+            "#,
+        )
+        .unwrap();
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        assert!((0..1000).all(|_| scenario.pick(&mut rng).name == "always"));
+    }
+
+    #[test]
+    fn rejects_scenarios_that_cant_run() {
+        let nothing = rejection("nothing", "entry = []");
+        assert!(nothing.ends_with("has nothing to do"));
+        let weightless = rejection(
+            "weightless",
+            "[[entry]]\nname = \"idle\"\nweight = 0\nstep = [{ path = \"/\" }]",
+        );
+        assert!(weightless.ends_with("has nothing to do"));
+        let no_steps = rejection("no-steps", "[[entry]]\nname = \"idle\"\nstep = []");
+        assert_eq!(no_steps, "idle has no steps");
+        let both = rejection(
+            "both",
+            "[[entry]]\nname = \"post\"\nstep = [{ path = \"/\", form = \"a=1\", json = \"{}\" }]",
+        );
+        assert_eq!(both, "post sends both a form and JSON");
+        let empty_before = rejection(
+            "empty-before",
+            r#"
+            [[entry]]
+            name = "index"
+            step = [{ path = "/", capture = [{ name = "id", after = "a", before = "" }] }]
+            "#,
+        );
+        assert_eq!(empty_before, "index captures up to nothing");
+        let unknown = rejection("unknown", "[[entry]]\nname = \"x\"\nstep = []\nsteps = []");
+        assert!(unknown.contains("unknown field"));
+    }
+
+    #[test]
+    fn the_shipped_scenarios_load() {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("scenarios");
+        for name in &["read-heavy.toml", "write-heavy.toml"] {
+            if let Err(e) = Scenario::load(&dir.join(name)) {
+                panic!("{}", e);
+            }
+        }
+    }
+}
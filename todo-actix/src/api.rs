@@ -0,0 +1,94 @@
+//! Routes for todo-web-core's JSON API.
+
+use crate::{header, into_http, lock_list, AppStateWithTodoList};
+use actix_web::{delete, error, get, patch, post, put, web, HttpRequest, HttpResponse};
+use todo_list::{IncomingTodo, TodoPatch};
+use todo_web_core::{api, HttpError, Response};
+
+/// Malformed JSON gets the same kind of body as every other API error.
+pub fn json_error(err: error::JsonPayloadError, _req: &HttpRequest) -> error::Error {
+    let response = match err {
+        error::JsonPayloadError::Overflow => into_http(api::too_large()),
+        _ => into_http(api::error(400, err.to_string())),
+    };
+    error::InternalError::from_response(err, response).into()
+}
+
+/// Sends back what the API decided on. The only thing that stops it getting that far is the todo
+/// list being poisoned, which the API has its own error for.
+fn respond(result: Result<Response, HttpError>) -> HttpResponse {
+    into_http(result.unwrap_or_else(|_| api::unavailable()))
+}
+
+#[get("/api/v1/todos")]
+async fn list_todos(
+    req: HttpRequest,
+    todo_state: web::Data<AppStateWithTodoList>,
+) -> HttpResponse {
+    respond(lock_list(&req, &todo_state).map(|mut list| api::list(&mut list)))
+}
+
+#[post("/api/v1/todos")]
+async fn create_todo(
+    req: HttpRequest,
+    todo_state: web::Data<AppStateWithTodoList>,
+    new: web::Json<IncomingTodo>,
+) -> HttpResponse {
+    let result = lock_list(&req, &todo_state)
+        .map(|mut list| api::create(&mut list, new.into_inner()));
+    respond(result)
+}
+
+#[get("/api/v1/todos/{id}")]
+async fn get_todo(
+    req: HttpRequest,
+    web::Path(id): web::Path<String>,
+    todo_state: web::Data<AppStateWithTodoList>,
+) -> HttpResponse {
+    respond(lock_list(&req, &todo_state).map(|mut list| api::get(&mut list, &id)))
+}
+
+#[put("/api/v1/todos/{id}")]
+async fn replace_todo(
+    req: HttpRequest,
+    web::Path(id): web::Path<String>,
+    todo_state: web::Data<AppStateWithTodoList>,
+    patch: web::Json<TodoPatch>,
+) -> HttpResponse {
+    let if_match = header(&req, "If-Match");
+    let result = lock_list(&req, &todo_state)
+        .map(|mut list| api::replace(&mut list, &id, patch.into_inner(), if_match));
+    respond(result)
+}
+
+#[patch("/api/v1/todos/{id}")]
+async fn patch_todo(
+    req: HttpRequest,
+    web::Path(id): web::Path<String>,
+    todo_state: web::Data<AppStateWithTodoList>,
+    patch: web::Json<TodoPatch>,
+) -> HttpResponse {
+    let if_match = header(&req, "If-Match");
+    let result = lock_list(&req, &todo_state)
+        .map(|mut list| api::update(&mut list, &id, patch.into_inner(), if_match));
+    respond(result)
+}
+
+#[delete("/api/v1/todos/{id}")]
+async fn delete_todo(
+    req: HttpRequest,
+    web::Path(id): web::Path<String>,
+    todo_state: web::Data<AppStateWithTodoList>,
+) -> HttpResponse {
+    respond(lock_list(&req, &todo_state).map(|mut list| api::delete(&mut list, &id)))
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.app_data(web::JsonConfig::default().error_handler(json_error))
+        .service(list_todos)
+        .service(create_todo)
+        .service(get_todo)
+        .service(replace_todo)
+        .service(patch_todo)
+        .service(delete_todo);
+}
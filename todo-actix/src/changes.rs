@@ -0,0 +1,77 @@
+//! `/ws`, a WebSocket that sends every change to the todo list as it happens, so open pages can
+//! keep up without reloading.
+
+use actix_codec::{Decoder, Encoder};
+use actix_http::ws::{self, CloseCode, CloseReason, Codec, Frame, Message};
+use actix_web::{get, web, Error, HttpRequest, HttpResponse};
+use bytes::BytesMut;
+use futures::future::ready;
+use futures::stream::{self, LocalBoxStream, StreamExt};
+use todo_web_core::Feed;
+
+/// Each change is a text message holding it as JSON. Nothing the client sends is listened to,
+/// beyond answering pings and closing when asked.
+#[get("/ws")]
+async fn changes(
+    req: HttpRequest,
+    payload: web::Payload,
+    feed: web::Data<Feed>,
+) -> Result<HttpResponse, Error> {
+    let mut response = ws::handshake(req.head())?;
+
+    let replies = frames(payload).filter_map(|frame| {
+        ready(match frame {
+            Frame::Ping(message) => Some(Message::Pong(message)),
+            Frame::Close(reason) => Some(Message::Close(reason)),
+            _ => None,
+        })
+    });
+    // The feed stops sending to a client that falls too far behind. Closing the socket then makes
+    // the page reload, which is how it catches up.
+    let fell_behind = CloseReason {
+        code: CloseCode::Again,
+        description: Some("fell too far behind".to_owned()),
+    };
+    let changes = feed
+        .subscribe()
+        .map(|event| Message::Text(event.data))
+        .chain(stream::once(ready(Message::Close(Some(fell_behind)))));
+    // Nothing more can be sent once the connection's been closed.
+    let messages = stream::select(replies, changes).scan(false, |closed, message| {
+        if *closed {
+            return ready(None);
+        }
+        *closed = matches!(message, Message::Close(_));
+        ready(Some(message))
+    });
+
+    let mut codec = Codec::new();
+    Ok(response.streaming(messages.map(move |message| {
+        let mut encoded = BytesMut::new();
+        codec.encode(message, &mut encoded)?;
+        Ok::<_, ws::ProtocolError>(encoded.freeze())
+    })))
+}
+
+/// The frames the client sends, until it sends something that isn't one or goes away.
+fn frames(payload: web::Payload) -> LocalBoxStream<'static, Frame> {
+    let state = (payload, BytesMut::new(), Codec::new());
+    stream::unfold(state, |(mut payload, mut buffer, mut codec)| async move {
+        loop {
+            match codec.decode(&mut buffer) {
+                Ok(Some(frame)) => return Some((frame, (payload, buffer, codec))),
+                Ok(None) => {}
+                Err(_) => return None,
+            }
+            match payload.next().await {
+                Some(Ok(bytes)) => buffer.extend_from_slice(&bytes),
+                _ => return None,
+            }
+        }
+    })
+    .boxed_local()
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(changes);
+}
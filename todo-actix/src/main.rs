@@ -1,203 +1,233 @@
-use actix_web::{error, get, post, web, App, Error, HttpResponse, HttpServer};
+use actix_web::dev::HttpResponseBuilder;
+use actix_web::http::StatusCode;
+use actix_web::{get, post, web, App, HttpRequest, HttpResponse, HttpServer};
 use actix_web::middleware::Logger;
 use env_logger::Env;
-use chrono::{NaiveDate, Utc};
-use std::collections::HashMap;
-use std::ops::DerefMut;
-use std::sync::Mutex;
-use tera::{Context, Tera};
-use todo_list::{IncomingTodo, Todo, TodoList};
-use uuid::Uuid;
+use chrono::Utc;
+use std::sync::{Arc, Mutex, MutexGuard};
+use todo_list::TodoList;
+use todo_web_core::{
+    open_list, pages, Assets, Config, Embedded, Feed, HttpError, PageResult, Response, Templates,
+    ACTOR_HEADER,
+};
+
+mod api;
+mod changes;
+
+/// The templates and static files built into the binary. `--dev` reads both kinds from disk
+/// instead, and `--templates` or `--static` reads just that kind.
+static EMBEDDED: Embedded = Embedded {
+    templates: &[
+        ("archived.html.j2", include_str!("../templates/archived.html.j2")),
+        ("detail.html.j2", include_str!("../templates/detail.html.j2")),
+        ("index.html.j2", include_str!("../templates/index.html.j2")),
+    ],
+    static_files: &[
+        ("site.css", include_bytes!("../static/site.css")),
+        ("site.js", include_bytes!("../static/site.js")),
+    ],
+};
 
 struct AppStateWithTodoList {
     list: Mutex<TodoList>,
+    trust_actor_header: bool,
 }
 
-fn redirect(to: &str) -> HttpResponse {
-    HttpResponse::Found().header("Location", to).finish()
+/// Sends back what todo-web-core decided on.
+fn into_http(response: Response) -> HttpResponse {
+    let status =
+        StatusCode::from_u16(response.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    let mut builder = HttpResponseBuilder::new(status);
+    for (name, value) in response.headers {
+        builder.header(name, value);
+    }
+    builder.content_type(response.content_type).body(response.body)
+}
+
+/// Sends back a page, or the error that stopped it in whatever form the client accepts.
+fn respond(req: &HttpRequest, result: PageResult) -> HttpResponse {
+    match result {
+        Ok(response) => into_http(response),
+        Err(e) => into_http(e.into_response(header(req, "Accept"))),
+    }
+}
+
+fn header<'a>(req: &'a HttpRequest, name: &str) -> Option<&'a str> {
+    req.headers().get(name).and_then(|value| value.to_str().ok())
+}
+
+/// The todo list, with whoever made `req` down as the one changing it.
+fn lock_list<'a>(
+    req: &HttpRequest,
+    todo_state: &'a AppStateWithTodoList,
+) -> Result<MutexGuard<'a, TodoList>, HttpError> {
+    let mut list = todo_state.list.lock().map_err(|_| HttpError::poisoned())?;
+    let actor = todo_web_core::actor(
+        todo_state.trust_actor_header,
+        header(req, ACTOR_HEADER),
+        req.peer_addr().map(|addr| addr.ip()),
+    );
+    list.set_actor(actor.as_deref());
+    Ok(list)
 }
 
 #[post("/new")]
 async fn post_new_todo(
+    req: HttpRequest,
     todo_state: web::Data<AppStateWithTodoList>,
-    query: web::Form<HashMap<String, String>>,
-) -> Result<HttpResponse, Error> {
-    let success = (|| {
-        let title = query.get("title")?;
-        let due_date = query.get("due-date")?;
-        let start_date = query.get("start-date")?;
-        let new = IncomingTodo {
-            title: title.to_string(),
-            startable: NaiveDate::parse_from_str(start_date, "%Y-%m-%d")
-                .unwrap_or_else(|_| NaiveDate::from_ymd(1970, 1, 1)),
-            due: NaiveDate::parse_from_str(due_date, "%Y-%m-%d")
-                .unwrap_or_else(|_| NaiveDate::from_ymd(1970, 1, 1)),
-        };
-        let mut mutexed_todo_state = todo_state.list.lock().unwrap();
-        let mutexed_todos = mutexed_todo_state.deref_mut();
-        mutexed_todos.add(new);
-        Some(())
-    })().is_some();
-
-    if success {
-        Ok(redirect("/"))
-    } else {
-        Err(error::ErrorInternalServerError("Whoops"))
-    }
+    templates: web::Data<Templates>,
+    body: web::Bytes,
+) -> HttpResponse {
+    let result = lock_list(&req, &todo_state)
+        .and_then(|mut list| pages::create(&templates, &mut list, &body));
+    respond(&req, result)
 }
 
 #[post("/edit/{id}")]
 async fn post_edit_todo(
+    req: HttpRequest,
     web::Path(id): web::Path<String>,
     todo_state: web::Data<AppStateWithTodoList>,
-    query: web::Form<HashMap<String, String>>,
-) -> Result<HttpResponse, Error> {
-    println!("POST /edit/{}", id);
-    let success = (|| {
-        // Get todo list
-        let mut mutexed_todo_state = todo_state.list.lock().unwrap();
-        let mutexed_todos = mutexed_todo_state.deref_mut();
-
-        // Failably retrive query parameters.
-        let title = query.get("title")?;
-        let due_date = query.get("due-date")?;
-        let start_date = query.get("start-date")?;
-        let uuid = Uuid::parse_str(&id).ok()?;
-        let existing = mutexed_todos.get(uuid)?;
-
-        // Update the todo if everything was pulled out and parsed ok.
-        let updated = Todo {
-            id: existing.id,
-            title: title.to_string(),
-            complete: existing.complete,
-            startable: NaiveDate::parse_from_str(start_date, "%Y-%m-%d")
-                .unwrap_or_else(|_| NaiveDate::from_ymd(1970, 1, 1)),
-            due: NaiveDate::parse_from_str(due_date, "%Y-%m-%d")
-                .unwrap_or_else(|_| NaiveDate::from_ymd(1970, 1, 1)),
-        };
-        mutexed_todos.update(updated);
-        Some(())
-    })().is_some();
-
-    if success {
-        Ok(redirect("/"))
-    } else {
-        Err(error::ErrorInternalServerError("Whoops"))
-    }
+    templates: web::Data<Templates>,
+    body: web::Bytes,
+) -> HttpResponse {
+    let if_match = header(&req, "If-Match");
+    let result = lock_list(&req, &todo_state)
+        .and_then(|mut list| pages::update(&templates, &mut list, &id, &body, if_match));
+    respond(&req, result)
 }
 
 #[get("/new")]
-async fn get_new_todo(tera: web::Data<tera::Tera>) -> Result<HttpResponse, Error> {
-    let mut context = Context::new();
-    context.insert("action", "Create");
-    let s = tera
-        .render("detail.html.j2", &context)
-        .map_err(|e| error::ErrorInternalServerError(format!("Template error: {:?}", e)))?;
-    Ok(HttpResponse::Ok().content_type("text/html").body(s))
+async fn get_new_todo(req: HttpRequest, templates: web::Data<Templates>) -> HttpResponse {
+    respond(&req, pages::new_todo(&templates))
 }
 
 #[get("/edit/{id}")]
 async fn get_edit_todo(
+    req: HttpRequest,
     web::Path(id): web::Path<String>,
     todo_state: web::Data<AppStateWithTodoList>,
-    tera: web::Data<tera::Tera>,
-) -> Result<HttpResponse, Error> {
-    println!("GET /edit/{}", id);
-    if let Ok(uuid) = Uuid::parse_str(&id) {
-        let mut mutexed_todos_state = todo_state.list.lock().unwrap();
-        let mutexed_todos = mutexed_todos_state.deref_mut();
-        if let Some(this_todo) = mutexed_todos.get(uuid) {
-            let mut context = Context::new();
-            context.insert("todo", &this_todo);
-            context.insert("action", "Update");
-            let s = tera
-                .render("detail.html.j2", &context)
-                .map_err(|e| error::ErrorInternalServerError(format!("Template error: {:?}", e)))?;
-            Ok(HttpResponse::Ok().content_type("text/html").body(s))
-        } else {
-            Err(error::ErrorNotFound("unknown uuid"))
-        }
-    } else {
-        Err(error::ErrorNotFound("bad uuid"))
-    }
+    templates: web::Data<Templates>,
+) -> HttpResponse {
+    let result = lock_list(&req, &todo_state)
+        .and_then(|mut list| pages::edit_todo(&templates, &mut list, &id));
+    respond(&req, result)
 }
 
 #[post("/complete/{id}")]
 async fn post_complete_todo(
+    req: HttpRequest,
     web::Path(id): web::Path<String>,
     todo_state: web::Data<AppStateWithTodoList>,
-) -> Result<HttpResponse, Error> {
-    println!("POST /complete/{}", id);
-    if let Ok(uuid) = Uuid::parse_str(&id) {
-        let mut mutexed_todos_state = todo_state.list.lock().unwrap();
-        let mutexed_todos = mutexed_todos_state.deref_mut();
-        mutexed_todos.toggle_completed(uuid);
-        let s = "{}";
-        Ok(HttpResponse::Ok().content_type("application/json").body(s))
-    } else {
-        Err(error::ErrorNotFound("unknown uuid"))
-    }
+) -> HttpResponse {
+    let result = lock_list(&req, &todo_state).and_then(|mut list| pages::toggle(&mut list, &id));
+    respond(&req, result)
 }
 
 #[get("/")]
 async fn get_index(
+    req: HttpRequest,
     todo_state: web::Data<AppStateWithTodoList>,
-    tera: web::Data<tera::Tera>,
-) -> Result<HttpResponse, Error> {
-    let mut context = Context::new();
-    let mut mutexed_todos = todo_state.list.lock().unwrap();
-    let all_todos = &mutexed_todos.deref_mut().get_all();
-    let mut today_todos = Vec::new();
-    let mut upcoming_todos = Vec::new();
+    templates: web::Data<Templates>,
+) -> HttpResponse {
     let today = Utc::today().naive_utc();
-    for a_todo in all_todos {
-        if a_todo.startable <= today {
-            today_todos.push(a_todo);
-        } else {
-            upcoming_todos.push(a_todo);
-        }
-    }
-    context.insert("today_todos", &today_todos);
-    context.insert("upcoming_todos", &upcoming_todos);
-    let s = tera
-        .render("index.html.j2", &context)
-        .map_err(|e| error::ErrorInternalServerError(format!("Template error: {:?}", e)))?;
-    // let s = "hello world".to_owned();
-    Ok(HttpResponse::Ok().content_type("text/html").body(s))
+    let result = lock_list(&req, &todo_state)
+        .and_then(|mut list| pages::index(&templates, &mut list, today));
+    respond(&req, result)
+}
+
+#[get("/archived")]
+async fn get_archived(
+    req: HttpRequest,
+    todo_state: web::Data<AppStateWithTodoList>,
+    templates: web::Data<Templates>,
+) -> HttpResponse {
+    let result = lock_list(&req, &todo_state)
+        .and_then(|mut list| pages::archived(&templates, &mut list));
+    respond(&req, result)
+}
+
+#[post("/delete/{id}")]
+async fn post_delete_todo(
+    req: HttpRequest,
+    web::Path(id): web::Path<String>,
+    todo_state: web::Data<AppStateWithTodoList>,
+) -> HttpResponse {
+    let result = lock_list(&req, &todo_state).and_then(|mut list| pages::delete(&mut list, &id));
+    respond(&req, result)
+}
+
+#[post("/archive/{id}")]
+async fn post_archive_todo(
+    req: HttpRequest,
+    web::Path(id): web::Path<String>,
+    todo_state: web::Data<AppStateWithTodoList>,
+) -> HttpResponse {
+    let result = lock_list(&req, &todo_state).and_then(|mut list| pages::archive(&mut list, &id));
+    respond(&req, result)
+}
+
+#[post("/unarchive/{id}")]
+async fn post_unarchive_todo(
+    req: HttpRequest,
+    web::Path(id): web::Path<String>,
+    todo_state: web::Data<AppStateWithTodoList>,
+) -> HttpResponse {
+    let result = lock_list(&req, &todo_state).and_then(|mut list| pages::unarchive(&mut list, &id));
+    respond(&req, result)
+}
+
+#[get("/static/{path:.*}")]
+async fn get_static(
+    req: HttpRequest,
+    web::Path(path): web::Path<String>,
+    assets: web::Data<Assets>,
+) -> HttpResponse {
+    respond(&req, assets.static_file(&path))
 }
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    env_logger::from_env(Env::default().default_filter_or("info")).init();
+    let config = Config::load_or_exit("0.0.0.0:8081");
+    env_logger::Builder::from_env(Env::default().default_filter_or(&config.log)).init();
+    let feed = Arc::new(Feed::new());
+    let mut list = open_list(&config);
+    feed.follow(&mut list);
     let todo_state = web::Data::new(AppStateWithTodoList {
-        list: Mutex::new(TodoList::new()),
+        list: Mutex::new(list),
+        trust_actor_header: config.trust_actor_header,
     });
+    let feed = web::Data::from(feed);
 
-    HttpServer::new(move || {
-        let mut tera = match Tera::new("templates/*.j2") {
-            Ok(t) => t,
-            Err(e) => {
-                println!("Parsing error(s): {}", e);
-                ::std::process::exit(1);
-            }
-        };
-        tera.autoescape_on(vec!["html.j2"]);
+    let assets = Assets::new(&config, &EMBEDDED);
+    let templates = web::Data::from(Templates::load_or_exit(&config, &assets));
+    let assets = web::Data::new(assets);
 
+    let mut server = HttpServer::new(move || {
         App::new()
             .wrap(Logger::default())
             // .wrap(Logger::new("%a %{User-Agent}i"))
             // .app_data(todos.clone())
             .app_data(todo_state.clone())
-            .data(tera)
+            .app_data(templates.clone())
             .service(get_index)
             .service(post_new_todo)
             .service(get_new_todo)
             .service(get_edit_todo)
             .service(post_edit_todo)
             .service(post_complete_todo)
-            .service(actix_files::Files::new("/static", "./static"))
-    })
-    .bind("0.0.0.0:8081")?
-    .run()
-    .await
+            .service(get_archived)
+            .service(post_delete_todo)
+            .service(post_archive_todo)
+            .service(post_unarchive_todo)
+            .configure(api::configure)
+            .configure(changes::configure)
+            .app_data(assets.clone())
+            .app_data(feed.clone())
+            .service(get_static)
+    });
+    if let Some(workers) = config.workers {
+        server = server.workers(workers);
+    }
+    server.bind(&config.listen)?.run().await
 }
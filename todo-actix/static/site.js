@@ -2,6 +2,9 @@
 	"use strict";
 
 	function toggleTodo(event) {
+		if (!event.target.classList.contains("todo-checkbox")) {
+			return;
+		}
 		let id = event.target.parentElement.id;
 		const options = {
 			method: "POST"
@@ -9,10 +12,143 @@
 		fetch("/complete/" + id, options);
 	}
 
+	function postButton(action, id, label) {
+		let form = document.createElement("form");
+		form.className = "inline-form";
+		form.method = "POST";
+		form.action = "/" + action + "/" + id;
+		let button = document.createElement("button");
+		button.type = "submit";
+		button.textContent = label;
+		form.append(button);
+		return form;
+	}
+
+	function time(date) {
+		let element = document.createElement("time");
+		element.dateTime = date;
+		element.textContent = date;
+		return element;
+	}
+
+	// The same list item index.html.j2 would have given the todo.
+	function todoItem(todo, startable) {
+		let item = document.createElement("li");
+		item.id = todo.id;
+		let checkbox = document.createElement("input");
+		checkbox.type = "checkbox";
+		checkbox.name = "complete";
+		if (startable) {
+			checkbox.className = "todo-checkbox";
+			checkbox.checked = todo.complete;
+		} else {
+			checkbox.disabled = true;
+		}
+		let title = document.createElement("span");
+		title.className = "todo-title";
+		title.textContent = todo.title;
+		let edit = document.createElement("a");
+		edit.href = "/edit/" + todo.id;
+		edit.textContent = "Edit";
+		item.append(checkbox, " ", title, " ");
+		if (!startable) {
+			item.append("Starts: ", time(todo.startable), " ");
+		}
+		item.append(
+			"Due: ", time(todo.due), " ",
+			edit, " ",
+			postButton("archive", todo.id, "Archive"), " ",
+			postButton("delete", todo.id, "Delete")
+		);
+		return item;
+	}
+
+	function removeTodo(id) {
+		let item = document.getElementById(id);
+		if (item) {
+			item.remove();
+		}
+	}
+
+	function showTodo(todo) {
+		// Dates are compared the way the server does, in UTC.
+		let today = new Date().toISOString().slice(0, 10);
+		let startable = todo.startable <= today;
+		let list = document.getElementById(startable ? "today-todos" : "upcoming-todos");
+		list.append(todoItem(todo, startable));
+	}
+
+	function applyChange(change) {
+		switch (change.event) {
+		case "created":
+		case "updated":
+			removeTodo(change.todo.id);
+			if (!change.todo.archived) {
+				showTodo(change.todo);
+			}
+			break;
+		case "toggled": {
+			let item = document.getElementById(change.todo.id);
+			let checkbox = item && item.querySelector(".todo-checkbox");
+			if (checkbox) {
+				checkbox.checked = change.todo.complete;
+			}
+			break;
+		}
+		case "deleted":
+			removeTodo(change.id);
+			break;
+		}
+		for (let id of ["today-todos", "upcoming-todos"]) {
+			let list = document.getElementById(id);
+			list.nextElementSibling.hidden = list.children.length > 0;
+		}
+	}
+
+	// Keeps the index up to date with changes made anywhere else. If the connection drops, the
+	// page may have missed some, so it's reloaded once the server is back.
+	function followWebSocket(reconnecting) {
+		let scheme = location.protocol === "https:" ? "wss:" : "ws:";
+		let socket = new WebSocket(scheme + "//" + location.host + "/ws");
+		let opened = false;
+		socket.addEventListener("open", function() {
+			if (reconnecting) {
+				location.reload();
+			}
+			opened = true;
+		});
+		socket.addEventListener("message", function(event) {
+			applyChange(JSON.parse(event.data));
+		});
+		socket.addEventListener("close", function() {
+			if (opened || reconnecting) {
+				setTimeout(followWebSocket, 2000, true);
+			}
+		});
+	}
+
+	// The same, over Server-Sent Events. The browser reconnects by itself and the server sends
+	// whatever was missed, unless it's forgotten some of it and says to start over.
+	function followEvents() {
+		let source = new EventSource("/events");
+		source.addEventListener("message", function(event) {
+			applyChange(JSON.parse(event.data));
+		});
+		source.addEventListener("reset", function() {
+			location.reload();
+		});
+	}
+
 	window.addEventListener("load", function() {
-		let elements = document.getElementsByClassName("todo-checkbox");
-		for (let el of elements) {
-			el.addEventListener("click", toggleTodo);
+		document.addEventListener("click", toggleTodo);
+		// Pages that can keep up say how.
+		switch (document.body.dataset.changes) {
+		case "websocket":
+			followWebSocket(false);
+			break;
+		case "events":
+			followEvents();
+			break;
 		}
 	});
-})();
\ No newline at end of file
+})();
@@ -0,0 +1,57 @@
+use crate::store::StoreError;
+use crate::Todo;
+use std::error::Error;
+use std::fmt;
+use uuid::Uuid;
+
+/// Why a `TodoList` operation didn't happen.
+#[derive(Debug)]
+pub enum TodoError {
+    /// There's no todo with this id.
+    NotFound(Uuid),
+    /// The due date is before the start date.
+    InvalidDates,
+    /// The title is empty or only whitespace.
+    EmptyTitle,
+    /// The title is longer than the list's limit, which is included.
+    TitleTooLong(usize),
+    /// The change doesn't make sense given the todo's current state, which is included so the
+    /// caller can show it.
+    Conflict(Todo),
+    /// The store couldn't be read or written.
+    Storage(StoreError),
+}
+
+impl fmt::Display for TodoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TodoError::NotFound(id) => write!(f, "no todo with id {}", id),
+            TodoError::InvalidDates => write!(f, "the due date is before the start date"),
+            TodoError::EmptyTitle => write!(f, "the title is empty"),
+            TodoError::TitleTooLong(max) => {
+                write!(f, "the title is longer than {} characters", max)
+            }
+            TodoError::Conflict(current) => write!(
+                f,
+                "the change conflicts with the current state of todo {}",
+                current.id
+            ),
+            TodoError::Storage(e) => e.fmt(f),
+        }
+    }
+}
+
+impl Error for TodoError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TodoError::Storage(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<StoreError> for TodoError {
+    fn from(e: StoreError) -> Self {
+        TodoError::Storage(e)
+    }
+}
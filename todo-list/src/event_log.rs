@@ -0,0 +1,408 @@
+use crate::file::write_atomically;
+use crate::store::{next_revision, StoreError, TodoStore, UpdateOutcome};
+use crate::Todo;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// One line of the log.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Event {
+    pub seq: u64,
+    pub at: DateTime<Utc>,
+    /// Who made the change, if they said.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actor: Option<String>,
+    #[serde(flatten)]
+    pub change: Change,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Change {
+    Added { todo: Todo },
+    Updated { todo: Todo },
+    Toggled { id: Uuid },
+    Deleted { id: Uuid },
+}
+
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+    seq: u64,
+    todos: Vec<Todo>,
+}
+
+/// Records every change as a line of JSON appended to a log file and rebuilds the current state
+/// by replaying that log on startup.
+///
+/// Every `compact_every` events the current state is written to `<log>.snapshot`, the events
+/// it covers are moved to `<log>.history`, and the log starts over. Startup only has to replay
+/// what came after the snapshot, while `history` still returns everything that ever happened.
+pub struct EventLogStore {
+    log_path: PathBuf,
+    log: File,
+    log_len: u64,
+    items: HashMap<Uuid, Todo>,
+    seq: u64,
+    events_since_snapshot: u64,
+    compact_every: u64,
+    /// Goes in every event from now on.
+    actor: Option<String>,
+}
+
+impl EventLogStore {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<EventLogStore, StoreError> {
+        let log_path = path.as_ref().to_path_buf();
+
+        let (mut seq, mut items) = match File::open(sibling(&log_path, "snapshot")) {
+            Ok(file) => {
+                let snapshot: Snapshot = serde_json::from_reader(BufReader::new(file))?;
+                let items = snapshot.todos.into_iter().map(|t| (t.id, t)).collect();
+                (snapshot.seq, items)
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (0, HashMap::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let (events, log_len) = read_events(&log_path)?;
+        let mut events_since_snapshot = 0;
+        for event in events {
+            // A crash part way through compaction can leave events in the log that the snapshot
+            // already includes.
+            if event.seq > seq {
+                seq = event.seq;
+                apply(&mut items, event.change);
+                events_since_snapshot += 1;
+            }
+        }
+
+        let log = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&log_path)?;
+        // Drop anything after the last complete event, which is what a crash mid-append leaves.
+        log.set_len(log_len)?;
+
+        Ok(EventLogStore {
+            log_path,
+            log,
+            log_len,
+            items,
+            seq,
+            events_since_snapshot,
+            compact_every: 1000,
+            actor: None,
+        })
+    }
+
+    /// How many events to let pile up before compacting on its own. Defaults to 1000.
+    pub fn compact_every(mut self, events: u64) -> EventLogStore {
+        self.compact_every = events.max(1);
+        self
+    }
+
+    /// Snapshots the current state and empties the log.
+    pub fn compact(&mut self) -> Result<(), StoreError> {
+        let mut todos: Vec<Todo> = self.items.values().cloned().collect();
+        todos.sort_by_key(|todo| (todo.startable, todo.id));
+        let snapshot = Snapshot {
+            seq: self.seq,
+            todos,
+        };
+        write_atomically(
+            &sibling(&self.log_path, "snapshot"),
+            &serde_json::to_vec(&snapshot)?,
+        )?;
+
+        let mut history = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(sibling(&self.log_path, "history"))?;
+        let history_len = history.metadata()?.len();
+        let events = fs::read(&self.log_path)?;
+        if let Err(e) = history
+            .write_all(&events[..self.log_len as usize])
+            .and_then(|_| history.sync_data())
+        {
+            // Same as in `record`: the next compaction mustn't append after half an event.
+            let _ = history.set_len(history_len);
+            return Err(e.into());
+        }
+
+        self.log.set_len(0)?;
+        self.log.sync_data()?;
+        self.log_len = 0;
+        self.events_since_snapshot = 0;
+        Ok(())
+    }
+
+    /// Every event ever recorded, oldest first, including those already compacted away.
+    pub fn history(&self) -> Result<Vec<Event>, StoreError> {
+        let (mut events, _) = read_events(&sibling(&self.log_path, "history"))?;
+        let (current, _) = read_events(&self.log_path)?;
+        events.extend(current);
+        // An interrupted compaction can copy the same events into the history twice.
+        let mut last_seq = 0;
+        events.retain(|event| {
+            let keep = event.seq > last_seq;
+            last_seq = last_seq.max(event.seq);
+            keep
+        });
+        Ok(events)
+    }
+
+    fn record(&mut self, change: Change) -> Result<(), StoreError> {
+        let event = Event {
+            seq: self.seq + 1,
+            at: Utc::now(),
+            actor: self.actor.clone(),
+            change,
+        };
+        let mut line = serde_json::to_vec(&event)?;
+        line.push(b'\n');
+        if let Err(e) = self
+            .log
+            .write_all(&line)
+            .and_then(|_| self.log.sync_data())
+        {
+            // Don't leave half an event for the next append to land after.
+            let _ = self.log.set_len(self.log_len);
+            return Err(e.into());
+        }
+        self.log_len += line.len() as u64;
+        self.seq = event.seq;
+        apply(&mut self.items, event.change);
+
+        self.events_since_snapshot += 1;
+        if self.events_since_snapshot >= self.compact_every {
+            // The event is safely in the log by now, so the change has been made whatever happens
+            // here. Compaction is tried again after the next event.
+            if let Err(e) = self.compact() {
+                log::warn!("couldn't compact {}: {}", self.log_path.display(), e);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TodoStore for EventLogStore {
+    fn add(&mut self, todo: Todo) -> Result<(), StoreError> {
+        self.record(Change::Added { todo })
+    }
+
+    fn get(&self, id: Uuid) -> Result<Option<Todo>, StoreError> {
+        Ok(self.items.get(&id).cloned())
+    }
+
+    fn update(&mut self, todo: Todo) -> Result<UpdateOutcome, StoreError> {
+        let next = match next_revision(&self.items, todo) {
+            Ok(next) => next,
+            Err(outcome) => return Ok(outcome),
+        };
+        self.record(Change::Updated { todo: next.clone() })?;
+        Ok(UpdateOutcome::Updated(next))
+    }
+
+    fn toggle_completed(&mut self, id: Uuid) -> Result<Option<Todo>, StoreError> {
+        if !self.items.contains_key(&id) {
+            return Ok(None);
+        }
+        self.record(Change::Toggled { id })?;
+        Ok(self.items.get(&id).cloned())
+    }
+
+    fn delete(&mut self, id: Uuid) -> Result<Option<Todo>, StoreError> {
+        let existing = self.items.get(&id).cloned();
+        if existing.is_some() {
+            self.record(Change::Deleted { id })?;
+        }
+        Ok(existing)
+    }
+
+    fn list(&self) -> Result<Vec<Todo>, StoreError> {
+        Ok(self.items.values().cloned().collect())
+    }
+
+    fn set_actor(&mut self, actor: Option<&str>) {
+        self.actor = actor.map(str::to_owned);
+    }
+}
+
+fn apply(items: &mut HashMap<Uuid, Todo>, change: Change) {
+    match change {
+        Change::Added { todo } | Change::Updated { todo } => {
+            items.insert(todo.id, todo);
+        }
+        Change::Toggled { id } => {
+            if let Some(todo) = items.get_mut(&id) {
+                todo.complete = !todo.complete;
+                todo.revision += 1;
+            }
+        }
+        Change::Deleted { id } => {
+            items.remove(&id);
+        }
+    }
+}
+
+/// Reads the events in `path` along with the length of the file up to the end of the last
+/// complete one. A final line without a newline is a torn write and is ignored; anything else
+/// that doesn't parse means the log is damaged and is reported as an error.
+fn read_events(path: &Path) -> Result<(Vec<Event>, u64), StoreError> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok((Vec::new(), 0)),
+        Err(e) => return Err(e.into()),
+    };
+    let mut reader = BufReader::new(file);
+    let mut events = Vec::new();
+    let mut good_len = 0;
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line)?;
+        if read == 0 || !line.ends_with(b"\n") {
+            break;
+        }
+        events.push(serde_json::from_slice(&line)?);
+        good_len += read as u64;
+    }
+    Ok((events, good_len))
+}
+
+/// `events.log` -> `events.log.<extension>`
+fn sibling(path: &Path, extension: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(".");
+    name.push(extension);
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{scratch, todo};
+
+    fn seqs(store: &EventLogStore) -> Vec<u64> {
+        store.history().unwrap().iter().map(|e| e.seq).collect()
+    }
+
+    /// Adds and toggles a todo, then compacts, then puts the log back the way it was: what a
+    /// crash after the snapshot is written but before the log is emptied leaves behind.
+    fn interrupted_compaction(path: &Path) -> Todo {
+        let item = todo("toggled");
+        let mut store = EventLogStore::open(path).unwrap();
+        store.add(item.clone()).unwrap();
+        store.toggle_completed(item.id).unwrap();
+        let log = fs::read(path).unwrap();
+        store.compact().unwrap();
+        drop(store);
+        fs::write(path, log).unwrap();
+        item
+    }
+
+    #[test]
+    fn replays_up_to_a_torn_final_line() {
+        let dir = scratch("torn");
+        let path = dir.join("events.log");
+        let first = todo("first");
+        let mut store = EventLogStore::open(&path).unwrap();
+        store.add(first.clone()).unwrap();
+        store.toggle_completed(first.id).unwrap();
+        drop(store);
+        let mut log = OpenOptions::new().append(true).open(&path).unwrap();
+        log.write_all(br#"{"seq":3,"at":"2021-08-02T00:00:00Z","event":"dele"#)
+            .unwrap();
+        drop(log);
+
+        let mut store = EventLogStore::open(&path).unwrap();
+        assert!(store.get(first.id).unwrap().unwrap().complete);
+        // The torn line is gone, so the next event doesn't land on the end of it.
+        store.add(todo("second")).unwrap();
+        drop(store);
+
+        let store = EventLogStore::open(&path).unwrap();
+        assert_eq!(store.list().unwrap().len(), 2);
+        assert_eq!(seqs(&store), [1, 2, 3]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn skips_events_the_snapshot_already_has() {
+        let dir = scratch("snapshot");
+        let path = dir.join("events.log");
+        let item = interrupted_compaction(&path);
+
+        let mut store = EventLogStore::open(&path).unwrap();
+        // Toggled once, not toggled back by replaying the toggle on top of the snapshot.
+        let reopened = store.get(item.id).unwrap().unwrap();
+        assert!(reopened.complete);
+        assert_eq!(reopened.revision, 2);
+        store.delete(item.id).unwrap();
+        assert_eq!(seqs(&store), [1, 2, 3]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn history_has_each_event_once_after_an_interrupted_compaction() {
+        let dir = scratch("history");
+        let path = dir.join("events.log");
+        interrupted_compaction(&path);
+
+        let mut store = EventLogStore::open(&path).unwrap();
+        store.compact().unwrap();
+        let (copied, _) = read_events(&sibling(&path, "history")).unwrap();
+        assert_eq!(copied.len(), 4);
+        assert_eq!(seqs(&store), [1, 2]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn keeps_a_change_when_compacting_after_it_fails() {
+        let dir = scratch("compact-fails");
+        let path = dir.join("events.log");
+        let snapshot = sibling(&path, "snapshot");
+        let mut store = EventLogStore::open(&path).unwrap().compact_every(1);
+        // The snapshot can't be renamed over a directory.
+        fs::create_dir(&snapshot).unwrap();
+
+        let item = todo("kept");
+        store.add(item.clone()).unwrap();
+        assert!(store.get(item.id).unwrap().is_some());
+
+        fs::remove_dir(&snapshot).unwrap();
+        store.toggle_completed(item.id).unwrap();
+        assert!(snapshot.is_file());
+        assert_eq!(fs::metadata(&path).unwrap().len(), 0);
+        drop(store);
+
+        let store = EventLogStore::open(&path).unwrap();
+        assert!(store.get(item.id).unwrap().unwrap().complete);
+        assert_eq!(seqs(&store), [1, 2]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn records_who_made_each_change() {
+        let dir = scratch("actor");
+        let path = dir.join("events.log");
+        let item = todo("audited");
+        let mut store = EventLogStore::open(&path).unwrap();
+        store.set_actor(Some("alice"));
+        store.add(item.clone()).unwrap();
+        store.set_actor(None);
+        store.toggle_completed(item.id).unwrap();
+        drop(store);
+
+        let store = EventLogStore::open(&path).unwrap();
+        let history = store.history().unwrap();
+        let actors: Vec<_> = history.iter().map(|e| e.actor.as_deref()).collect();
+        assert_eq!(actors, [Some("alice"), None]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
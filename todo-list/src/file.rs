@@ -0,0 +1,168 @@
+use crate::store::{next_revision, StoreError, TodoStore, UpdateOutcome};
+use crate::Todo;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// Keeps every todo in memory and rewrites the whole collection to a JSON file after each
+/// mutation. Good enough for a team-sized list, and the file is easy to read or fix by hand.
+pub struct JsonFileStore {
+    path: PathBuf,
+    items: HashMap<Uuid, Todo>,
+}
+
+impl JsonFileStore {
+    /// Loads the todos in `path`, or starts empty if the file doesn't exist yet. The file itself
+    /// isn't created until the first mutation.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<JsonFileStore, StoreError> {
+        let path = path.as_ref().to_path_buf();
+        let items = match File::open(&path) {
+            Ok(file) => {
+                let todos: Vec<Todo> = serde_json::from_reader(io::BufReader::new(file))?;
+                todos.into_iter().map(|todo| (todo.id, todo)).collect()
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(JsonFileStore { path, items })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Writes `items` to disk and only then makes it the current state, so a failed write leaves
+    /// memory and the file agreeing with each other.
+    fn commit(&mut self, items: HashMap<Uuid, Todo>) -> Result<(), StoreError> {
+        let mut todos: Vec<&Todo> = items.values().collect();
+        todos.sort_by_key(|todo| (todo.startable, todo.id));
+        write_atomically(&self.path, &serde_json::to_vec_pretty(&todos)?)?;
+        self.items = items;
+        Ok(())
+    }
+}
+
+impl TodoStore for JsonFileStore {
+    fn add(&mut self, todo: Todo) -> Result<(), StoreError> {
+        let mut items = self.items.clone();
+        items.insert(todo.id, todo);
+        self.commit(items)
+    }
+
+    fn get(&self, id: Uuid) -> Result<Option<Todo>, StoreError> {
+        Ok(self.items.get(&id).cloned())
+    }
+
+    fn update(&mut self, todo: Todo) -> Result<UpdateOutcome, StoreError> {
+        let next = match next_revision(&self.items, todo) {
+            Ok(next) => next,
+            Err(outcome) => return Ok(outcome),
+        };
+        let mut items = self.items.clone();
+        items.insert(next.id, next.clone());
+        self.commit(items)?;
+        Ok(UpdateOutcome::Updated(next))
+    }
+
+    fn delete(&mut self, id: Uuid) -> Result<Option<Todo>, StoreError> {
+        let mut items = self.items.clone();
+        let removed = items.remove(&id);
+        if removed.is_some() {
+            self.commit(items)?;
+        }
+        Ok(removed)
+    }
+
+    fn list(&self) -> Result<Vec<Todo>, StoreError> {
+        Ok(self.items.values().cloned().collect())
+    }
+}
+
+/// Replaces the contents of `path` with `contents` without ever leaving a half-written file
+/// behind: the data goes to a temporary file in the same directory, is flushed to disk, and is
+/// then renamed over the original.
+pub(crate) fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+    let mut tmp_name = std::ffi::OsString::from(".");
+    tmp_name.push(file_name);
+    tmp_name.push(".tmp");
+    let tmp_path = dir.join(tmp_name);
+
+    let mut tmp = File::create(&tmp_path)?;
+    tmp.write_all(contents)?;
+    tmp.sync_all()?;
+    drop(tmp);
+    fs::rename(&tmp_path, path)?;
+
+    // Make the rename itself durable. Directories can't be opened like this on every platform,
+    // so failing here isn't worth reporting.
+    if let Ok(dir) = File::open(dir) {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{scratch, todo};
+
+    #[test]
+    fn starts_empty_without_creating_the_file() {
+        let dir = scratch("json-missing");
+        let path = dir.join("todos.json");
+        let store = JsonFileStore::open(&path).unwrap();
+        assert!(store.list().unwrap().is_empty());
+        assert!(!path.exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn reopening_reads_back_what_was_written() {
+        let dir = scratch("json-reopen");
+        let path = dir.join("todos.json");
+        let kept = todo("kept");
+        let gone = todo("gone");
+        let mut store = JsonFileStore::open(&path).unwrap();
+        store.add(kept.clone()).unwrap();
+        store.add(gone.clone()).unwrap();
+        store.toggle_completed(kept.id).unwrap();
+        store.delete(gone.id).unwrap();
+        drop(store);
+
+        let store = JsonFileStore::open(&path).unwrap();
+        let todos = store.list().unwrap();
+        assert_eq!(todos.len(), 1);
+        assert_eq!(todos[0].id, kept.id);
+        assert!(todos[0].complete);
+        assert_eq!(todos[0].revision, 2);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn a_failed_write_leaves_memory_alone() {
+        let dir = scratch("json-failed");
+        let path = dir.join("todos.json");
+        let first = todo("first");
+        let mut store = JsonFileStore::open(&path).unwrap();
+        store.add(first.clone()).unwrap();
+        // With the directory gone there's nowhere to put the temporary file.
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(store.add(todo("second")).is_err());
+        assert!(store.toggle_completed(first.id).is_err());
+        assert!(store.delete(first.id).is_err());
+        let todos = store.list().unwrap();
+        assert_eq!(todos.len(), 1);
+        assert_eq!(todos[0].id, first.id);
+        assert!(!todos[0].complete);
+    }
+}
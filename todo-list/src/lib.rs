@@ -1,8 +1,23 @@
 use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 use uuid::Uuid;
 
+mod error;
+mod event_log;
+mod file;
+mod sqlite;
+mod store;
+#[cfg(test)]
+mod test_support;
+mod validate;
+
+pub use error::TodoError;
+pub use event_log::{Change, Event, EventLogStore};
+pub use file::JsonFileStore;
+pub use sqlite::SqliteStore;
+pub use store::{MemoryStore, StoreError, TodoStore, UpdateOutcome};
+pub use validate::{Limits, TodoForm, ValidationErrors};
+
 #[derive(Debug, Deserialize)]
 pub struct IncomingTodo {
     pub title: String,
@@ -17,10 +32,69 @@ pub struct Todo {
     pub complete: bool,
     pub startable: NaiveDate,
     pub due: NaiveDate,
+    /// Archived todos are kept but left off the index.
+    #[serde(default)]
+    pub archived: bool,
+    /// Goes up by one every time the todo changes, so an edit based on an old copy can be spotted
+    /// and turned away instead of silently overwriting someone else's work.
+    #[serde(default)]
+    pub revision: u64,
+}
+
+/// A partial update, as sent by API clients: every field that's present replaces the todo's
+/// current value, and every field that's missing leaves it alone.
+#[derive(Debug, Default, Deserialize)]
+pub struct TodoPatch {
+    pub title: Option<String>,
+    pub startable: Option<NaiveDate>,
+    pub due: Option<NaiveDate>,
+    pub complete: Option<bool>,
+    pub archived: Option<bool>,
+    /// The revision the change is based on, for clients that can't send `If-Match`.
+    pub revision: Option<u64>,
+}
+
+impl TodoPatch {
+    /// `todo` with every field this patch sets replaced. The revision is left alone; the caller
+    /// decides what the change was based on.
+    pub fn apply(self, todo: Todo) -> Todo {
+        Todo {
+            title: self.title.unwrap_or(todo.title),
+            startable: self.startable.unwrap_or(todo.startable),
+            due: self.due.unwrap_or(todo.due),
+            complete: self.complete.unwrap_or(todo.complete),
+            archived: self.archived.unwrap_or(todo.archived),
+            ..todo
+        }
+    }
+
+    /// Whether this sets every field a client can edit, as a full replacement has to.
+    pub fn is_complete(&self) -> bool {
+        self.title.is_some()
+            && self.startable.is_some()
+            && self.due.is_some()
+            && self.complete.is_some()
+            && self.archived.is_some()
+    }
+}
+
+/// Something that was just done to a `TodoList`, as passed to whatever is watching it.
+/// Archiving and unarchiving are updates.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum TodoChange {
+    Created { todo: Todo },
+    Updated { todo: Todo },
+    Toggled { todo: Todo },
+    Deleted { id: Uuid },
 }
 
+type Watcher = Box<dyn Fn(&TodoChange) + Send + Sync>;
+
 pub struct TodoList {
-    items: HashMap<Uuid, Todo>,
+    store: Box<dyn TodoStore>,
+    limits: Limits,
+    watchers: Vec<Watcher>,
 }
 
 impl Default for TodoList {
@@ -30,56 +104,137 @@ impl Default for TodoList {
 }
 
 impl TodoList {
+    /// A list backed by a `MemoryStore`, so nothing survives a restart.
     pub fn new() -> TodoList {
+        TodoList::with_store(MemoryStore::new())
+    }
+
+    pub fn with_store<S: TodoStore + 'static>(store: S) -> TodoList {
         TodoList {
-            items: HashMap::new(),
+            store: Box::new(store),
+            limits: Limits::default(),
+            watchers: Vec::new(),
         }
     }
-    pub fn add(&mut self, new: IncomingTodo) -> Todo {
+
+    /// Replaces the default `Limits` that every added or updated todo is checked against.
+    pub fn with_limits(mut self, limits: Limits) -> TodoList {
+        self.limits = limits;
+        self
+    }
+
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// Says who's making the changes from here on, for stores that keep an audit trail of them,
+    /// like `EventLogStore`.
+    pub fn set_actor(&mut self, actor: Option<&str>) {
+        self.store.set_actor(actor);
+    }
+
+    /// Calls `watcher` with every change made to the list from now on, once it's been stored.
+    pub fn watch<F>(&mut self, watcher: F)
+    where
+        F: Fn(&TodoChange) + Send + Sync + 'static,
+    {
+        self.watchers.push(Box::new(watcher));
+    }
+
+    fn changed(&self, change: TodoChange) {
+        for watcher in &self.watchers {
+            watcher(&change);
+        }
+    }
+
+    pub fn add(&mut self, new: IncomingTodo) -> Result<Todo, TodoError> {
+        self.check(&new.title, new.startable, new.due)?;
         let created = Todo {
             id: Uuid::new_v4(),
             title: new.title,
             complete: false,
             startable: new.startable,
             due: new.due,
+            archived: false,
+            revision: 1,
         };
         let result = created.clone();
-        self.items.insert(created.id, created);
-        result
-    }
-
-    pub fn toggle_completed(&mut self, id: Uuid) {
-        let maybe_item = self.items.get(&id);
-        println!("found item: {:?}", maybe_item);
-        if let Some(item) = maybe_item {
-            let toggled_item = Todo {
-                id: item.id,
-                title: item.title.clone(),
-                complete: !item.complete,
-                startable: item.startable,
-                due: item.due,
-            };
-            println!("updated item: {:?}", toggled_item);
-            let old_item = self.items.insert(toggled_item.id, toggled_item);
-            println!("old item from hashmap: {:?}", old_item);
-            let gotten_item = self.items.get(&id);
-            println!("what you get from the hashmap: {:?}", gotten_item);
-        };
+        self.store.add(created)?;
+        self.changed(TodoChange::Created {
+            todo: result.clone(),
+        });
+        Ok(result)
     }
 
-    pub fn update(&mut self, existing: Todo) {
-        self.items.insert(existing.id, existing);
+    /// Returns the toggled todo.
+    pub fn toggle_completed(&mut self, id: Uuid) -> Result<Todo, TodoError> {
+        let toggled = self.store.toggle_completed(id)?;
+        let toggled = toggled.ok_or(TodoError::NotFound(id))?;
+        self.changed(TodoChange::Toggled {
+            todo: toggled.clone(),
+        });
+        Ok(toggled)
     }
 
-    pub fn get_all(&mut self) -> Vec<Todo> {
-        let mut result = Vec::with_capacity(self.items.len());
-        for todo in self.items.values() {
-            result.push(todo.clone());
+    /// Replaces the todo with the same id as `existing`, provided `existing.revision` is still
+    /// the current one. If it isn't, the error is a `Conflict` holding what's there now. Returns
+    /// the todo as stored, with its new revision.
+    pub fn update(&mut self, existing: Todo) -> Result<Todo, TodoError> {
+        self.check(&existing.title, existing.startable, existing.due)?;
+        let id = existing.id;
+        match self.store.update(existing)? {
+            UpdateOutcome::Updated(todo) => {
+                self.changed(TodoChange::Updated { todo: todo.clone() });
+                Ok(todo)
+            }
+            UpdateOutcome::NotFound => Err(TodoError::NotFound(id)),
+            UpdateOutcome::Stale(current) => Err(TodoError::Conflict(current)),
         }
-        result
     }
 
-    pub fn get(&mut self, id: Uuid) -> Option<Todo> {
-        self.items.get(&id).cloned()
+    /// Removes a todo for good and returns what was removed.
+    pub fn delete(&mut self, id: Uuid) -> Result<Todo, TodoError> {
+        let deleted = self.store.delete(id)?.ok_or(TodoError::NotFound(id))?;
+        self.changed(TodoChange::Deleted { id });
+        Ok(deleted)
+    }
+
+    /// Returns the archived todo. Archiving something that's already archived is a `Conflict`.
+    pub fn archive(&mut self, id: Uuid) -> Result<Todo, TodoError> {
+        self.set_archived(id, true)
+    }
+
+    /// Returns the unarchived todo. Unarchiving something that isn't archived is a `Conflict`.
+    pub fn unarchive(&mut self, id: Uuid) -> Result<Todo, TodoError> {
+        self.set_archived(id, false)
+    }
+
+    fn set_archived(&mut self, id: Uuid, archived: bool) -> Result<Todo, TodoError> {
+        let item = self.get(id)?;
+        if item.archived == archived {
+            return Err(TodoError::Conflict(item));
+        }
+        self.update(Todo { archived, ..item })
+    }
+
+    /// Every todo, archived or not.
+    pub fn get_all(&mut self) -> Result<Vec<Todo>, TodoError> {
+        Ok(self.store.list()?)
+    }
+
+    pub fn get_archived(&mut self) -> Result<Vec<Todo>, TodoError> {
+        let mut archived = self.store.list()?;
+        archived.retain(|todo| todo.archived);
+        Ok(archived)
+    }
+
+    pub fn get(&mut self, id: Uuid) -> Result<Todo, TodoError> {
+        self.store.get(id)?.ok_or(TodoError::NotFound(id))
+    }
+
+    /// The rules every todo has to follow, whether it's new or being edited.
+    fn check(&self, title: &str, startable: NaiveDate, due: NaiveDate) -> Result<(), TodoError> {
+        self.limits.check_title(title)?;
+        validate::check_dates(startable, due)
     }
 }
@@ -0,0 +1,285 @@
+use crate::store::{StoreError, TodoStore, UpdateOutcome};
+use crate::Todo;
+use rusqlite::{params, Connection, OptionalExtension, Row, Transaction};
+use std::path::Path;
+use std::sync::Mutex;
+use uuid::Uuid;
+
+/// Schema changes, oldest first. The database's `user_version` records how many of these have
+/// been applied, so add new steps to the end and never edit one that has shipped.
+const MIGRATIONS: &[&str] = &["
+    CREATE TABLE todos (
+        id TEXT PRIMARY KEY NOT NULL,
+        title TEXT NOT NULL,
+        complete INTEGER NOT NULL DEFAULT 0,
+        startable TEXT NOT NULL,
+        due TEXT NOT NULL
+    );
+    CREATE INDEX todos_startable ON todos (startable);
+    CREATE INDEX todos_due ON todos (due);
+    CREATE INDEX todos_complete ON todos (complete);
+", "
+    ALTER TABLE todos ADD COLUMN archived INTEGER NOT NULL DEFAULT 0;
+    CREATE INDEX todos_archived ON todos (archived);
+", "
+    ALTER TABLE todos ADD COLUMN revision INTEGER NOT NULL DEFAULT 0;
+"];
+
+const SELECT_TODO: &str =
+    "SELECT id, title, complete, startable, due, archived, revision FROM todos";
+
+/// Keeps todos in a SQLite database file. SQLite is compiled into the binary, so there's no
+/// server to run alongside the app.
+pub struct SqliteStore {
+    // `Connection` can be sent between threads but not shared by them.
+    conn: Mutex<Connection>,
+}
+
+impl SqliteStore {
+    /// Opens (creating if necessary) the database at `path` and brings its schema up to date.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<SqliteStore, StoreError> {
+        SqliteStore::from_connection(Connection::open(path)?)
+    }
+
+    /// A database that lives only as long as the store does. Handy for trying things out.
+    pub fn open_in_memory() -> Result<SqliteStore, StoreError> {
+        SqliteStore::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(mut conn: Connection) -> Result<SqliteStore, StoreError> {
+        migrate(&mut conn)?;
+        Ok(SqliteStore {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    fn conn(&mut self) -> &mut Connection {
+        self.conn.get_mut().unwrap()
+    }
+}
+
+fn migrate(conn: &mut Connection) -> Result<(), StoreError> {
+    let applied: i64 = conn.query_row("PRAGMA user_version", params![], |row| row.get(0))?;
+    for (version, migration) in MIGRATIONS.iter().enumerate().skip(applied as usize) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration)?;
+        // PRAGMA doesn't take bound parameters.
+        tx.execute_batch(&format!("PRAGMA user_version = {}", version + 1))?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+fn todo_from_row(row: &Row) -> rusqlite::Result<Todo> {
+    let id: String = row.get(0)?;
+    Ok(Todo {
+        id: Uuid::parse_str(&id).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e))
+        })?,
+        title: row.get(1)?,
+        complete: row.get(2)?,
+        startable: row.get(3)?,
+        due: row.get(4)?,
+        archived: row.get(5)?,
+        revision: row.get::<_, i64>(6)? as u64,
+    })
+}
+
+fn get_in(tx: &Transaction, id: Uuid) -> rusqlite::Result<Option<Todo>> {
+    tx.query_row(
+        &format!("{} WHERE id = ?1", SELECT_TODO),
+        params![id.to_string()],
+        todo_from_row,
+    )
+    .optional()
+}
+
+impl TodoStore for SqliteStore {
+    fn add(&mut self, todo: Todo) -> Result<(), StoreError> {
+        self.conn().execute(
+            "INSERT INTO todos (id, title, complete, startable, due, archived, revision)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                todo.id.to_string(),
+                todo.title,
+                todo.complete,
+                todo.startable,
+                todo.due,
+                todo.archived,
+                todo.revision as i64
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn get(&self, id: Uuid) -> Result<Option<Todo>, StoreError> {
+        let conn = self.conn.lock().unwrap();
+        let todo = conn
+            .query_row(
+                &format!("{} WHERE id = ?1", SELECT_TODO),
+                params![id.to_string()],
+                todo_from_row,
+            )
+            .optional()?;
+        Ok(todo)
+    }
+
+    fn update(&mut self, todo: Todo) -> Result<UpdateOutcome, StoreError> {
+        let tx = self.conn().transaction()?;
+        // The revision check and the write happen in this one statement, so there's no window
+        // for another connection to slip a change in between them.
+        let changed = tx.execute(
+            "UPDATE todos
+             SET title = ?2, complete = ?3, startable = ?4, due = ?5, archived = ?6,
+                 revision = revision + 1
+             WHERE id = ?1 AND revision = ?7",
+            params![
+                todo.id.to_string(),
+                todo.title,
+                todo.complete,
+                todo.startable,
+                todo.due,
+                todo.archived,
+                todo.revision as i64
+            ],
+        )?;
+        let current = get_in(&tx, todo.id)?;
+        tx.commit()?;
+        Ok(match current {
+            Some(current) if changed > 0 => UpdateOutcome::Updated(current),
+            Some(current) => UpdateOutcome::Stale(current),
+            None => UpdateOutcome::NotFound,
+        })
+    }
+
+    fn toggle_completed(&mut self, id: Uuid) -> Result<Option<Todo>, StoreError> {
+        let tx = self.conn().transaction()?;
+        tx.execute(
+            "UPDATE todos SET complete = NOT complete, revision = revision + 1 WHERE id = ?1",
+            params![id.to_string()],
+        )?;
+        let toggled = get_in(&tx, id)?;
+        tx.commit()?;
+        Ok(toggled)
+    }
+
+    fn delete(&mut self, id: Uuid) -> Result<Option<Todo>, StoreError> {
+        let tx = self.conn().transaction()?;
+        let existing = get_in(&tx, id)?;
+        if existing.is_some() {
+            tx.execute("DELETE FROM todos WHERE id = ?1", params![id.to_string()])?;
+        }
+        tx.commit()?;
+        Ok(existing)
+    }
+
+    fn list(&self) -> Result<Vec<Todo>, StoreError> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(&format!("{} ORDER BY startable, due", SELECT_TODO))?;
+        let todos = statement
+            .query_map(params![], todo_from_row)?
+            .collect::<rusqlite::Result<Vec<Todo>>>()?;
+        Ok(todos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{scratch, todo};
+
+    #[test]
+    fn migrates_a_version_1_database_and_keeps_its_rows() {
+        let dir = scratch("sqlite-v1");
+        let path = dir.join("todos.db");
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(MIGRATIONS[0]).unwrap();
+        conn.execute_batch("PRAGMA user_version = 1").unwrap();
+        let id = Uuid::new_v4();
+        conn.execute(
+            "INSERT INTO todos (id, title, complete, startable, due)
+             VALUES (?1, 'old', 1, '2021-08-02', '2021-08-04')",
+            params![id.to_string()],
+        )
+        .unwrap();
+        drop(conn);
+
+        let store = SqliteStore::open(&path).unwrap();
+        let conn = store.conn.lock().unwrap();
+        let version: i64 = conn
+            .query_row("PRAGMA user_version", params![], |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, MIGRATIONS.len() as i64);
+        let columns = conn
+            .prepare("SELECT name FROM pragma_table_info('todos')")
+            .unwrap()
+            .query_map(params![], |row| row.get::<_, String>(0))
+            .unwrap()
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .unwrap();
+        assert!(columns.iter().any(|c| c == "archived"));
+        assert!(columns.iter().any(|c| c == "revision"));
+        drop(conn);
+
+        let kept = store.get(id).unwrap().unwrap();
+        assert_eq!(kept.title, "old");
+        assert!(kept.complete);
+        assert!(!kept.archived);
+        assert_eq!(kept.revision, 0);
+        drop(store);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn a_stale_update_is_left_alone() {
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        let item = todo("first");
+        store.add(item.clone()).unwrap();
+        let mut edited = item.clone();
+        edited.title = "second".to_owned();
+        match store.update(edited).unwrap() {
+            UpdateOutcome::Updated(current) => assert_eq!(current.revision, 2),
+            other => panic!("expected Updated, got {:?}", other),
+        }
+
+        let mut stale = item;
+        stale.title = "third".to_owned();
+        match store.update(stale).unwrap() {
+            UpdateOutcome::Stale(current) => {
+                assert_eq!(current.title, "second");
+                assert_eq!(current.revision, 2);
+            }
+            other => panic!("expected Stale, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn updating_a_missing_todo_is_not_found() {
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        assert!(matches!(store.update(todo("ghost")).unwrap(), UpdateOutcome::NotFound));
+    }
+
+    #[test]
+    fn toggling_bumps_the_revision() {
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        let item = todo("toggled");
+        store.add(item.clone()).unwrap();
+        let toggled = store.toggle_completed(item.id).unwrap().unwrap();
+        assert!(toggled.complete);
+        assert_eq!(toggled.revision, 2);
+        let back = store.toggle_completed(item.id).unwrap().unwrap();
+        assert!(!back.complete);
+        assert_eq!(back.revision, 3);
+        assert!(store.toggle_completed(Uuid::new_v4()).unwrap().is_none());
+    }
+
+    #[test]
+    fn delete_returns_what_it_removed() {
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        let item = todo("doomed");
+        store.add(item.clone()).unwrap();
+        assert_eq!(store.delete(item.id).unwrap().unwrap().title, "doomed");
+        assert!(store.get(item.id).unwrap().is_none());
+        assert!(store.delete(item.id).unwrap().is_none());
+    }
+}
@@ -0,0 +1,156 @@
+use crate::Todo;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use uuid::Uuid;
+
+/// Somewhere to keep todos. `TodoList` does the bookkeeping (assigning ids, toggling, and so on)
+/// and hands the results to one of these, so the web servers never need to know where their
+/// todos actually live.
+pub trait TodoStore: Send + Sync {
+    fn add(&mut self, todo: Todo) -> Result<(), StoreError>;
+    fn get(&self, id: Uuid) -> Result<Option<Todo>, StoreError>;
+    /// Replaces the stored todo with the same id, as long as it's still at `todo.revision`.
+    /// What gets stored has the next revision number.
+    fn update(&mut self, todo: Todo) -> Result<UpdateOutcome, StoreError>;
+    fn delete(&mut self, id: Uuid) -> Result<Option<Todo>, StoreError>;
+    fn list(&self) -> Result<Vec<Todo>, StoreError>;
+
+    /// Says who's making the changes from here on, for stores that keep a record of that. Most
+    /// don't, and ignore it.
+    fn set_actor(&mut self, _actor: Option<&str>) {}
+
+    /// Flips `complete` on a todo and returns the result, or `None` if there is no such todo.
+    /// Stores that can do this in one step should, rather than relying on this read-then-write.
+    fn toggle_completed(&mut self, id: Uuid) -> Result<Option<Todo>, StoreError> {
+        loop {
+            let item = match self.get(id)? {
+                Some(item) => item,
+                None => return Ok(None),
+            };
+            let toggled = Todo {
+                complete: !item.complete,
+                ..item
+            };
+            match self.update(toggled)? {
+                UpdateOutcome::Updated(todo) => return Ok(Some(todo)),
+                UpdateOutcome::NotFound => return Ok(None),
+                // Someone got in between the read and the write; go again.
+                UpdateOutcome::Stale(_) => continue,
+            }
+        }
+    }
+}
+
+/// What a `TodoStore::update` did.
+#[derive(Debug)]
+pub enum UpdateOutcome {
+    /// The todo was replaced. This is what's stored now.
+    Updated(Todo),
+    /// There's no todo with that id.
+    NotFound,
+    /// The todo has moved on from the revision the update was based on, so it was left alone.
+    /// This is what's stored now.
+    Stale(Todo),
+}
+
+/// The version of `todo` that should replace what's in `items`, or why nothing should.
+pub(crate) fn next_revision(items: &HashMap<Uuid, Todo>, todo: Todo) -> Result<Todo, UpdateOutcome> {
+    match items.get(&todo.id) {
+        Some(current) if current.revision == todo.revision => Ok(Todo {
+            revision: todo.revision + 1,
+            ..todo
+        }),
+        Some(current) => Err(UpdateOutcome::Stale(current.clone())),
+        None => Err(UpdateOutcome::NotFound),
+    }
+}
+
+#[derive(Debug)]
+pub enum StoreError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    Sqlite(rusqlite::Error),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StoreError::Io(e) => write!(f, "storage I/O error: {}", e),
+            StoreError::Json(e) => write!(f, "storage format error: {}", e),
+            StoreError::Sqlite(e) => write!(f, "database error: {}", e),
+        }
+    }
+}
+
+impl Error for StoreError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            StoreError::Io(e) => Some(e),
+            StoreError::Json(e) => Some(e),
+            StoreError::Sqlite(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for StoreError {
+    fn from(e: io::Error) -> Self {
+        StoreError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for StoreError {
+    fn from(e: serde_json::Error) -> Self {
+        StoreError::Json(e)
+    }
+}
+
+impl From<rusqlite::Error> for StoreError {
+    fn from(e: rusqlite::Error) -> Self {
+        StoreError::Sqlite(e)
+    }
+}
+
+/// The original storage: a `HashMap` that is gone as soon as the process exits.
+#[derive(Default)]
+pub struct MemoryStore {
+    items: HashMap<Uuid, Todo>,
+}
+
+impl MemoryStore {
+    pub fn new() -> MemoryStore {
+        MemoryStore {
+            items: HashMap::new(),
+        }
+    }
+}
+
+impl TodoStore for MemoryStore {
+    fn add(&mut self, todo: Todo) -> Result<(), StoreError> {
+        self.items.insert(todo.id, todo);
+        Ok(())
+    }
+
+    fn get(&self, id: Uuid) -> Result<Option<Todo>, StoreError> {
+        Ok(self.items.get(&id).cloned())
+    }
+
+    fn update(&mut self, todo: Todo) -> Result<UpdateOutcome, StoreError> {
+        match next_revision(&self.items, todo) {
+            Ok(next) => {
+                self.items.insert(next.id, next.clone());
+                Ok(UpdateOutcome::Updated(next))
+            }
+            Err(outcome) => Ok(outcome),
+        }
+    }
+
+    fn delete(&mut self, id: Uuid) -> Result<Option<Todo>, StoreError> {
+        Ok(self.items.remove(&id))
+    }
+
+    fn list(&self) -> Result<Vec<Todo>, StoreError> {
+        Ok(self.items.values().cloned().collect())
+    }
+}
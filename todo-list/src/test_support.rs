@@ -0,0 +1,29 @@
+//! Fixtures shared by the stores' tests.
+
+use crate::Todo;
+use chrono::NaiveDate;
+use std::fs;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+/// An empty directory of the test's own for a store's files and everything beside them. Tests
+/// remove it when they're done.
+pub(crate) fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("todo-list-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// A new todo at its first revision, due two days after it can be started.
+pub(crate) fn todo(title: &str) -> Todo {
+    Todo {
+        id: Uuid::new_v4(),
+        title: title.to_owned(),
+        complete: false,
+        startable: NaiveDate::from_ymd_opt(2021, 8, 2).unwrap(),
+        due: NaiveDate::from_ymd_opt(2021, 8, 4).unwrap(),
+        archived: false,
+        revision: 1,
+    }
+}
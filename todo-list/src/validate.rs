@@ -0,0 +1,214 @@
+use crate::error::TodoError;
+use crate::IncomingTodo;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// The knobs on what counts as an acceptable todo.
+#[derive(Clone, Debug)]
+pub struct Limits {
+    /// Longest title allowed, in characters.
+    pub max_title_chars: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_title_chars: 200,
+        }
+    }
+}
+
+impl Limits {
+    pub(crate) fn check_title(&self, title: &str) -> Result<(), TodoError> {
+        if title.trim().is_empty() {
+            Err(TodoError::EmptyTitle)
+        } else if title.chars().count() > self.max_title_chars {
+            Err(TodoError::TitleTooLong(self.max_title_chars))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+pub(crate) fn check_dates(startable: NaiveDate, due: NaiveDate) -> Result<(), TodoError> {
+    if due < startable {
+        Err(TodoError::InvalidDates)
+    } else {
+        Ok(())
+    }
+}
+
+/// What someone typed into the todo form, before any of it has been checked. It serializes with
+/// the same field names as `Todo`, so a template can show it back to them in place of one.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TodoForm {
+    pub title: String,
+    #[serde(rename(deserialize = "start-date"))]
+    pub startable: String,
+    #[serde(rename(deserialize = "due-date"))]
+    pub due: String,
+    /// The revision of the todo the form was filled in from. Empty on the create form.
+    pub revision: String,
+}
+
+impl TodoForm {
+    /// Parses the dates and checks everything against `limits`, collecting a message for each
+    /// field that's wrong rather than stopping at the first.
+    pub fn parse(&self, limits: &Limits) -> Result<IncomingTodo, ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        let startable = parse_date(&self.startable, "start date", "startable", &mut errors);
+        let due = parse_date(&self.due, "due date", "due", &mut errors);
+        if let Err(e) = limits.check_title(&self.title) {
+            errors.add("title", e.to_string());
+        }
+        if let (Some(startable), Some(due)) = (startable, due) {
+            if let Err(e) = check_dates(startable, due) {
+                errors.add("due", e.to_string());
+            }
+            if errors.is_empty() {
+                return Ok(IncomingTodo {
+                    title: self.title.clone(),
+                    startable,
+                    due,
+                });
+            }
+        }
+        Err(errors)
+    }
+}
+
+fn parse_date(
+    value: &str,
+    description: &str,
+    field: &'static str,
+    errors: &mut ValidationErrors,
+) -> Option<NaiveDate> {
+    if value.trim().is_empty() {
+        errors.add(field, format!("the {} is required", description));
+        return None;
+    }
+    match NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d") {
+        Ok(date) => Some(date),
+        Err(_) => {
+            errors.add(
+                field,
+                format!("the {} should look like 2021-08-02, not {:?}", description, value),
+            );
+            None
+        }
+    }
+}
+
+/// A message for each field that failed validation, keyed by the name of the `Todo` field it
+/// belongs to.
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(transparent)]
+pub struct ValidationErrors {
+    fields: BTreeMap<&'static str, String>,
+}
+
+impl ValidationErrors {
+    fn add(&mut self, field: &'static str, message: String) {
+        self.fields.entry(field).or_insert(message);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    pub fn get(&self, field: &str) -> Option<&str> {
+        self.fields.get(field).map(String::as_str)
+    }
+}
+
+impl fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let messages: Vec<&str> = self.fields.values().map(String::as_str).collect();
+        write!(f, "{}", messages.join("; "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn form(title: &str, startable: &str, due: &str) -> TodoForm {
+        TodoForm {
+            title: title.to_owned(),
+            startable: startable.to_owned(),
+            due: due.to_owned(),
+            revision: String::new(),
+        }
+    }
+
+    fn errors(form: &TodoForm) -> ValidationErrors {
+        form.parse(&Limits { max_title_chars: 5 }).unwrap_err()
+    }
+
+    #[test]
+    fn a_good_form_parses() {
+        let todo = form("pasta", " 2021-08-02 ", "2021-08-02")
+            .parse(&Limits::default())
+            .unwrap();
+        assert_eq!(todo.title, "pasta");
+        assert_eq!(todo.startable, NaiveDate::from_ymd_opt(2021, 8, 2).unwrap());
+        assert_eq!(todo.due, todo.startable);
+    }
+
+    #[test]
+    fn both_dates_are_required() {
+        let errors = errors(&form("pasta", "", "  "));
+        assert_eq!(errors.get("startable"), Some("the start date is required"));
+        assert_eq!(errors.get("due"), Some("the due date is required"));
+        assert_eq!(errors.get("title"), None);
+    }
+
+    #[test]
+    fn a_date_has_to_be_year_month_day() {
+        let errors = errors(&form("pasta", "08/02/2021", "2021-02-30"));
+        assert_eq!(
+            errors.get("startable"),
+            Some("the start date should look like 2021-08-02, not \"08/02/2021\"")
+        );
+        assert_eq!(
+            errors.get("due"),
+            Some("the due date should look like 2021-08-02, not \"2021-02-30\"")
+        );
+    }
+
+    #[test]
+    fn due_cant_be_before_start() {
+        let errors = errors(&form("pasta", "2021-08-04", "2021-08-02"));
+        assert_eq!(errors.get("due"), Some("the due date is before the start date"));
+        assert_eq!(errors.get("startable"), None);
+    }
+
+    #[test]
+    fn a_title_has_to_be_there_and_not_too_long() {
+        let blank = errors(&form(" \t", "2021-08-02", "2021-08-04"));
+        assert_eq!(blank.get("title"), Some("the title is empty"));
+        let long = errors(&form("linguine", "2021-08-02", "2021-08-04"));
+        assert_eq!(long.get("title"), Some("the title is longer than 5 characters"));
+        // The limit is in characters, not bytes.
+        assert!(form("\u{2713}\u{2713}\u{2713}", "2021-08-02", "2021-08-04")
+            .parse(&Limits { max_title_chars: 3 })
+            .is_ok());
+    }
+
+    #[test]
+    fn every_field_gets_one_message() {
+        let errors = errors(&form("", "nope", "2021-08-02"));
+        assert_eq!(errors.get("title"), Some("the title is empty"));
+        assert!(errors.get("startable").is_some());
+        assert_eq!(errors.get("due"), None);
+        assert_eq!(errors.to_string().matches("; ").count(), 1);
+
+        let mut twice = ValidationErrors::default();
+        twice.add("due", "first".to_owned());
+        twice.add("due", "second".to_owned());
+        assert_eq!(twice.get("due"), Some("first"));
+    }
+}
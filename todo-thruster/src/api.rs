@@ -0,0 +1,100 @@
+//! Routes for todo-web-core's JSON API. Every path, body and status code here matches todo-actix's
+//! API exactly, so the two servers can be benchmarked and tested against each other.
+
+use crate::{into_context, lock_list, path_id, read_body, request_header, Ctx};
+use serde::de::DeserializeOwned;
+use thruster::{middleware_fn, MiddlewareNext, MiddlewareResult};
+use todo_list::{IncomingTodo, TodoPatch};
+use todo_web_core::{api, HttpError, Response};
+
+/// Reads the request body as JSON, no further than `JSON_LIMIT`. Whatever goes wrong, the response
+/// to send instead comes back along with the context.
+async fn json_body<T: DeserializeOwned>(mut context: Ctx) -> Result<(T, Ctx), (Response, Ctx)> {
+    let content_type = request_header(&context, "Content-Type");
+    if let Err(response) = api::expect_json(content_type.as_deref()) {
+        return Err((response, context));
+    }
+    let body = match read_body(&mut context, api::JSON_LIMIT).await {
+        Ok(Some(body)) => body,
+        Ok(None) => return Err((api::too_large(), context)),
+        Err(_) => {
+            return Err((
+                api::error(400, "Error that occur during reading payload"),
+                context,
+            ))
+        }
+    };
+    match api::parse_json(content_type.as_deref(), &body) {
+        Ok(value) => Ok((value, context)),
+        Err(response) => Err((response, context)),
+    }
+}
+
+/// Sends back what the API decided on. The only thing that stops it getting that far is the todo
+/// list being poisoned, which the API has its own error for.
+#[allow(clippy::result_large_err)]
+fn respond(context: Ctx, result: Result<Response, HttpError>) -> MiddlewareResult<Ctx> {
+    Ok(into_context(context, result.unwrap_or_else(|_| api::unavailable())))
+}
+
+#[middleware_fn]
+pub(crate) async fn list_todos(context: Ctx, _next: MiddlewareNext<Ctx>) -> MiddlewareResult<Ctx> {
+    let result = lock_list(&context).map(|mut todos| api::list(&mut todos));
+    respond(context, result)
+}
+
+#[middleware_fn]
+pub(crate) async fn create_todo(context: Ctx, _next: MiddlewareNext<Ctx>) -> MiddlewareResult<Ctx> {
+    match json_body::<IncomingTodo>(context).await {
+        Ok((new, context)) => {
+            let result = lock_list(&context).map(|mut todos| api::create(&mut todos, new));
+            respond(context, result)
+        }
+        Err((response, context)) => Ok(into_context(context, response)),
+    }
+}
+
+#[middleware_fn]
+pub(crate) async fn get_todo(context: Ctx, _next: MiddlewareNext<Ctx>) -> MiddlewareResult<Ctx> {
+    let id = path_id(&context);
+    let result = lock_list(&context).map(|mut todos| api::get(&mut todos, &id));
+    respond(context, result)
+}
+
+/// PUT and PATCH only differ in whether every field has to be there.
+async fn update_todo(context: Ctx, replace: bool) -> MiddlewareResult<Ctx> {
+    let id = path_id(&context);
+    let if_match = request_header(&context, "If-Match");
+    let (patch, context) = match json_body::<TodoPatch>(context).await {
+        Ok(read) => read,
+        Err((response, context)) => return Ok(into_context(context, response)),
+    };
+    let result = lock_list(&context).map(|mut todos| {
+        if replace {
+            api::replace(&mut todos, &id, patch, if_match.as_deref())
+        } else {
+            api::update(&mut todos, &id, patch, if_match.as_deref())
+        }
+    });
+    respond(context, result)
+}
+
+#[middleware_fn]
+pub(crate) async fn replace_todo(
+    context: Ctx,
+    _next: MiddlewareNext<Ctx>,
+) -> MiddlewareResult<Ctx> {
+    update_todo(context, true).await
+}
+
+#[middleware_fn]
+pub(crate) async fn patch_todo(context: Ctx, _next: MiddlewareNext<Ctx>) -> MiddlewareResult<Ctx> {
+    update_todo(context, false).await
+}
+
+#[middleware_fn]
+pub(crate) async fn delete_todo(context: Ctx, _next: MiddlewareNext<Ctx>) -> MiddlewareResult<Ctx> {
+    let id = path_id(&context);
+    let result = lock_list(&context).map(|mut todos| api::delete(&mut todos, &id));
+    respond(context, result)
+}
@@ -0,0 +1,41 @@
+//! `/events`, a Server-Sent Events stream of every change to the todo list, for pages that want to
+//! keep up without reloading and for tools tailing the list.
+
+use crate::{request_header, Ctx};
+use futures::stream::{self, StreamExt};
+use hyper::Body;
+use std::convert::Infallible;
+use thruster::{middleware_fn, Context as _, MiddlewareNext, MiddlewareResult};
+use todo_web_core::{Feed, FeedEvent};
+
+fn message(feed: &Feed, event: FeedEvent) -> String {
+    format!("id: {}\ndata: {}\n\n", feed.event_id(event.id), event.data)
+}
+
+/// Each change is an event holding it as JSON, numbered so a client that sends `Last-Event-ID`
+/// when it reconnects is sent what it missed first. If some of that has been forgotten, it's sent
+/// a `reset` event instead and should start over from the list as it is now.
+#[middleware_fn]
+pub async fn get_events(mut context: Ctx, _next: MiddlewareNext<Ctx>) -> MiddlewareResult<Ctx> {
+    let feed = context.extra.feed.clone();
+    let last_id = request_header(&context, "Last-Event-ID").filter(|id| !id.trim().is_empty());
+    let resumed = feed.resume(last_id.as_deref());
+    let last_id = feed.event_id(resumed.last_id);
+
+    // An event with nothing but an id isn't passed on, but it tells the client where it's up to.
+    let first = if resumed.gap {
+        vec![format!("id: {}\nevent: reset\ndata: {{}}\n\n", last_id)]
+    } else if resumed.missed.is_empty() {
+        vec![format!("id: {}\n\n", last_id)]
+    } else {
+        let missed = resumed.missed.into_iter();
+        missed.map(|event| message(&feed, event)).collect()
+    };
+    let events = resumed.events.map(move |event| message(&feed, event));
+    let messages = stream::iter(first).chain(events);
+
+    context.body = Body::wrap_stream(messages.map(Ok::<_, Infallible>));
+    context.content_type("text/event-stream");
+    context.set("Cache-Control", "no-cache");
+    Ok(context)
+}
@@ -1,34 +1,61 @@
-use chrono::{NaiveDate, Utc};
-use hyper::Body;
-use tera::{Context, Tera};
+use chrono::Utc;
+use hyper::body::HttpBody as _;
+use hyper::{Body, Method};
 use thruster::context::hyper_request::HyperRequest;
 use thruster::context::typed_hyper_context::TypedHyperContext;
 use thruster::hyper_server::HyperServer;
-use thruster::middleware::query_params::query_params;
 use thruster::{async_middleware, middleware_fn};
-use thruster::{App, ThrusterServer};
+use thruster::{App, Context as _, ThrusterServer};
 use thruster::{MiddlewareNext, MiddlewareResult};
-use thruster::middleware::file::file;
-use todo_list::{IncomingTodo, Todo, TodoList};
-use uuid::Uuid;
+use todo_list::TodoList;
+use todo_web_core::{
+    open_list, pages, Assets, Config, Embedded, Feed, FormError, HttpError, PageResult, Response,
+    Templates, ACTOR_HEADER, MAX_FORM_BYTES,
+};
 use std::time::Instant;
 use log::info;
 use env_logger::Env;
 
-use std::collections::HashMap;
-use std::sync::{Arc, RwLock};
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock, RwLockWriteGuard};
 use thruster::errors::ThrusterError;
 
+mod api;
+mod events;
+
+/// The templates and static files built into the binary. `--dev` reads both kinds from disk
+/// instead, and `--templates` or `--static` reads just that kind.
+static EMBEDDED: Embedded = Embedded {
+    templates: &[
+        ("archived.html.j2", include_str!("../templates/archived.html.j2")),
+        ("detail.html.j2", include_str!("../templates/detail.html.j2")),
+        ("index.html.j2", include_str!("../templates/index.html.j2")),
+    ],
+    static_files: &[
+        ("site.css", include_bytes!("../static/site.css")),
+        ("site.js", include_bytes!("../static/site.js")),
+    ],
+};
+
+use api::{create_todo, delete_todo, get_todo, list_todos, patch_todo, replace_todo};
+use events::get_events;
+
 type Ctx = TypedHyperContext<RequestConfig>;
 
 struct ServerConfig {
-    tera: Arc<RwLock<tera::Tera>>,
+    templates: Arc<Templates>,
     todos: Arc<RwLock<TodoList>>,
+    assets: Arc<Assets>,
+    feed: Arc<Feed>,
+    trust_actor_header: bool,
 }
 
 struct RequestConfig {
-    tera: Arc<RwLock<tera::Tera>>,
+    templates: Arc<Templates>,
     todos: Arc<RwLock<TodoList>>,
+    assets: Arc<Assets>,
+    feed: Arc<Feed>,
+    trust_actor_header: bool,
 }
 
 // I grabbed this function from the revision history of src/context/basic_hyper_context.rs because the author removed it as part of a "bug fix" that broke one of the examples.
@@ -46,14 +73,25 @@ fn generate_context(request: HyperRequest, state: &ServerConfig, _path: &str) ->
     Ctx::new(
         request,
         RequestConfig {
-            tera: state.tera.clone(),
+            templates: state.templates.clone(),
             todos: state.todos.clone(),
+            assets: state.assets.clone(),
+            feed: state.feed.clone(),
+            trust_actor_header: state.trust_actor_header,
         },
     )
 }
 
+/// Anything without a route of its own. Thruster 1.1's router never matches "/" once there's a
+/// catch-all, so a GET for it ends up here too and gets the index.
 #[middleware_fn]
 async fn not_found_404(mut context: Ctx, _next: MiddlewareNext<Ctx>) -> MiddlewareResult<Ctx> {
+    let is_index = context.hyper_request.as_ref().is_some_and(|req| {
+        req.request.method() == Method::GET && req.request.uri().path() == "/"
+    });
+    if is_index {
+        return index(context);
+    }
     context.body = Body::from(
         "<!DOCTYPE html>
     <html lang=\"en\">
@@ -72,219 +110,209 @@ async fn not_found_404(mut context: Ctx, _next: MiddlewareNext<Ctx>) -> Middlewa
     Ok(context)
 }
 
-#[middleware_fn]
-async fn post_new_todo(context: Ctx, _next: MiddlewareNext<Ctx>) -> MiddlewareResult<Ctx> {
-    match context.get_body().await {
-        Ok((body, mut context)) => {
-            let form_data = parse_form_data(body);
-            // Because of the question marks, the closure should return false as soon as one of the
-            // hashmap lookups returns None.
-            let success = (|| {
-                // Extract body data.
-                let title = form_data.get("title")?;
-                let due_date = form_data.get("due-date")?;
-                let start_date = form_data.get("start-date")?;
-
-                // Get todo list struct.
-                let todos = context.extra.todos.clone();
-                let mut todos = todos.write().unwrap();
-                let new = IncomingTodo {
-                    title: title.to_string(),
-                    startable: NaiveDate::parse_from_str(start_date, "%Y-%m-%d")
-                        .unwrap_or_else(|_| NaiveDate::from_ymd(1970, 1, 1)),
-                    due: NaiveDate::parse_from_str(due_date, "%Y-%m-%d")
-                        .unwrap_or_else(|_| NaiveDate::from_ymd(1970, 1, 1)),
-                };
-                todos.add(new);
-
-                Some(())
-            })()
-            .is_some();
-
-            return if success {
-                context.redirect("/index.html");
-                Ok(context)
-            } else {
-                context.body = Body::from("request error");
-                Ok(context)
-            };
-        }
-        Err(e) => panic!("unrecoverable error: {:?}", e),
+/// Puts what todo-web-core decided on into the response.
+fn into_context(mut context: Ctx, response: Response) -> Ctx {
+    for (name, value) in &response.headers {
+        context.set(name, value);
+    }
+    context.content_type(response.content_type);
+    context.status(u32::from(response.status));
+    context.body = Body::from(response.body);
+    context
+}
+
+fn thruster_error(context: Ctx, e: HttpError) -> ThrusterError<Ctx> {
+    ThrusterError {
+        context,
+        message: e.message,
+        status: u32::from(e.status),
+        cause: None,
+    }
+}
+
+/// Sends back a page, or hands the error that stopped it on to `error_responses`.
+#[allow(clippy::result_large_err)]
+fn respond(context: Ctx, result: PageResult) -> MiddlewareResult<Ctx> {
+    match result {
+        Ok(response) => Ok(into_context(context, response)),
+        Err(e) => Err(thruster_error(context, e)),
     }
 }
 
-fn parse_form_data(body: String) -> HashMap<String, String> {
-    let mut form_hash = HashMap::new();
+/// The todo list, with whoever made the request down as the one changing it.
+fn lock_list(context: &Ctx) -> Result<RwLockWriteGuard<'_, TodoList>, HttpError> {
+    let mut todos = context.extra.todos.write().map_err(|_| HttpError::poisoned())?;
+    let peer = context.hyper_request.as_ref().and_then(|req| req.ip);
+    let named = request_header(context, ACTOR_HEADER);
+    let actor = todo_web_core::actor(context.extra.trust_actor_header, named.as_deref(), peer);
+    todos.set_actor(actor.as_deref());
+    Ok(todos)
+}
+
+/// The `:id` path segment. Thruster 1.1 routes on `:id` without ever filling in `params`, so this
+/// falls back to the last segment of the path, which is where every route here puts the id. A
+/// missing one is as bad an id as any other.
+fn path_id(context: &Ctx) -> String {
+    if let Some(id) = context.params.as_ref().and_then(|params| params.get("id")) {
+        return id.clone();
+    }
+    context
+        .hyper_request
+        .as_ref()
+        .and_then(|req| req.request.uri().path().rsplit('/').next())
+        .unwrap_or_default()
+        .to_owned()
+}
 
-    {
-        for query_piece in body.split('&') {
-            let mut query_iterator = query_piece.split('=');
-            let key = query_iterator.next().unwrap().to_owned();
+/// The value of a request header, if it was sent and is valid UTF-8.
+fn request_header(context: &Ctx, name: &str) -> Option<String> {
+    let req = context.hyper_request.as_ref()?;
+    let value = req.request.headers().get(name)?;
+    value.to_str().ok().map(str::to_owned)
+}
 
-            match query_iterator.next() {
-                Some(val) => form_hash.insert(key, val.to_owned()),
-                None => form_hash.insert(key, "true".to_owned()),
-            };
+/// Reads the whole request body, unless it's more than `limit` bytes. One that says up front it's
+/// too big isn't read at all, and one that doesn't say is only read until it's gone past `limit`;
+/// either way it comes back as `None`.
+async fn read_body(context: &mut Ctx, limit: usize) -> Result<Option<Vec<u8>>, hyper::Error> {
+    let length = request_header(context, "Content-Length").and_then(|len| len.parse().ok());
+    if length.is_some_and(|length: usize| length > limit) {
+        return Ok(None);
+    }
+    let mut body = match context.hyper_request.as_mut() {
+        Some(req) => std::mem::take(req.request.body_mut()),
+        None => return Ok(Some(Vec::new())),
+    };
+    let mut read = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        if read.len() + chunk.len() > limit {
+            return Ok(None);
         }
+        read.extend_from_slice(&chunk);
     }
+    Ok(Some(read))
+}
 
-    form_hash
+/// Reads a submitted form, turning it away with a 413 if it's too big to be one.
+async fn read_form(mut context: Ctx) -> Result<(Vec<u8>, Ctx), ThrusterError<Ctx>> {
+    match read_body(&mut context, MAX_FORM_BYTES).await {
+        Ok(Some(body)) => Ok((body, context)),
+        Ok(None) => Err(thruster_error(context, FormError::TooLarge.into())),
+        Err(_) => Err(thruster_error(
+            context,
+            HttpError::new(400, "couldn't read the request body"),
+        )),
+    }
 }
 
+/// Turns an error from anything further down the chain into a response, so that a bad request
+/// or a failure on our side never gets further than this.
 #[middleware_fn]
-async fn post_edit_todo(context: Ctx, _next: MiddlewareNext<Ctx>) -> MiddlewareResult<Ctx> {
-    if let Ok((body, mut context)) = context.get_body().await {
-        let form_data = parse_form_data(body);
-        // Because of the question marks, the closure should return false as soon as one of the
-        // hashmap lookups returns None.
-        let success = (|| {
-            // Extract body data, URL params.
-            let title = form_data.get("title")?;
-            let due_date = form_data.get("due-date")?;
-            let start_date = form_data.get("start-date")?;
-            let id_string = context.query_params.get("id")?;
-            let id = Uuid::parse_str(&id_string).ok()?;
-
-            // Get todo list struct.
-            let todos = context.extra.todos.clone();
-            let mut todos = todos.write().unwrap();
-
-            if let Some(existing) = todos.get(id) {
-                let updated = Todo {
-                    id: existing.id,
-                    title: title.to_string(),
-                    complete: existing.complete,
-                    startable: NaiveDate::parse_from_str(start_date, "%Y-%m-%d")
-                        .unwrap_or_else(|_| NaiveDate::from_ymd(1970, 1, 1)),
-                    due: NaiveDate::parse_from_str(due_date, "%Y-%m-%d")
-                        .unwrap_or_else(|_| NaiveDate::from_ymd(1970, 1, 1)),
-                };
-                todos.update(updated);
-            }
-            Some(())
-        })()
-        .is_some();
-
-        return if success {
-            context.redirect("/index.html");
-            Ok(context)
-        } else {
-            context.body = Body::from("request error");
-            Ok(context)
-        };
-    } else {
-        panic!("unrecoverable error")
-    }
+async fn error_responses(context: Ctx, next: MiddlewareNext<Ctx>) -> MiddlewareResult<Ctx> {
+    let e = match next(context).await {
+        Ok(context) => return Ok(context),
+        Err(e) => e,
+    };
+    let accept = request_header(&e.context, "Accept");
+    let response = HttpError::new(e.status as u16, e.message).into_response(accept.as_deref());
+    Ok(into_context(e.context, response))
 }
 
 #[middleware_fn]
-async fn get_new_todo(
-    mut request_context: Ctx,
-    _next: MiddlewareNext<Ctx>,
-) -> MiddlewareResult<Ctx> {
-    let tera = request_context.extra.tera.clone();
-    let tera = tera.read().unwrap();
-    let mut tpl_context = Context::new();
-    tpl_context.insert("action", "Create");
-    if let Ok(s) = tera.render("detail.html.j2", &tpl_context) {
-        request_context.body = Body::from(s);
-    } else {
-        request_context.body = Body::from("oops");
-    }
+async fn post_new_todo(context: Ctx, _next: MiddlewareNext<Ctx>) -> MiddlewareResult<Ctx> {
+    let (body, context) = read_form(context).await?;
+    let templates = &context.extra.templates;
+    let result =
+        lock_list(&context).and_then(|mut todos| pages::create(templates, &mut todos, &body));
+    respond(context, result)
+}
 
-    Ok(request_context)
+#[middleware_fn]
+async fn post_edit_todo(context: Ctx, _next: MiddlewareNext<Ctx>) -> MiddlewareResult<Ctx> {
+    let if_match = request_header(&context, "If-Match");
+    let id = path_id(&context);
+    let (body, context) = read_form(context).await?;
+    let templates = &context.extra.templates;
+    let result = lock_list(&context).and_then(|mut todos| {
+        pages::update(templates, &mut todos, &id, &body, if_match.as_deref())
+    });
+    respond(context, result)
 }
 
 #[middleware_fn]
-async fn get_edit_todo(mut context: Ctx, _next: MiddlewareNext<Ctx>) -> MiddlewareResult<Ctx> {
-    let success = (|| {
-        let todos = context.extra.todos.clone();
-        let mut todos = todos.write().unwrap();
-
-        let id = context.query_params.get("id")?;
-        let uuid = Uuid::parse_str(&id).ok()?;
-        if let Some(this_todo) = todos.get(uuid) {
-            let mut tera_context = Context::new();
-            tera_context.insert("todo", &this_todo);
-            tera_context.insert("action", "Update");
-            let tera = context.extra.tera.clone();
-            let tera = tera.read().unwrap();
-            let s = tera.render("detail.html.j2", &tera_context).ok()?;
-            context.body = Body::from(s);
-        } else {
-            context.body = Body::from("unknown todo id");
-        }
-        Some(())
-    })()
-    .is_some();
-
-    if success {
-        Ok(context)
-    } else {
-        Err(ThrusterError {
-            context,
-            message: "invalid request".to_string(),
-            status: 400,
-            cause: None,
-        })
-    }
+async fn get_new_todo(context: Ctx, _next: MiddlewareNext<Ctx>) -> MiddlewareResult<Ctx> {
+    let result = pages::new_todo(&context.extra.templates);
+    respond(context, result)
 }
 
 #[middleware_fn]
-async fn post_complete_todo(mut context: Ctx, _next: MiddlewareNext<Ctx>) -> MiddlewareResult<Ctx> {
-    let success = (|| {
-        let todos = context.extra.todos.clone();
-        let mut todos = todos.write().unwrap();
-
-        let id = context.query_params.get("id")?;
-        let uuid = Uuid::parse_str(&id).ok()?;
-        todos.toggle_completed(uuid);
-        context.body = Body::from("{}");
-        Some(())
-    })()
-    .is_some();
-
-    if success {
-        Ok(context)
-    } else {
-        Err(ThrusterError {
-            context,
-            message: "invalid request".to_string(),
-            status: 400,
-            cause: None,
-        })
-    }
+async fn get_edit_todo(context: Ctx, _next: MiddlewareNext<Ctx>) -> MiddlewareResult<Ctx> {
+    let id = path_id(&context);
+    let templates = &context.extra.templates;
+    let result =
+        lock_list(&context).and_then(|mut todos| pages::edit_todo(templates, &mut todos, &id));
+    respond(context, result)
 }
 
 #[middleware_fn]
-async fn get_index(mut req_context: Ctx, _next: MiddlewareNext<Ctx>) -> MiddlewareResult<Ctx> {
-    let tera = req_context.extra.tera.clone();
-    let tera = tera.read().unwrap();
-    let todos = req_context.extra.todos.clone();
-    let mut todos = todos.write().unwrap();
-    let mut tpl_context = Context::new();
-
-    let all_todos = todos.get_all();
-    let mut today_todos = Vec::new();
-    let mut upcoming_todos = Vec::new();
+async fn post_complete_todo(context: Ctx, _next: MiddlewareNext<Ctx>) -> MiddlewareResult<Ctx> {
+    let id = path_id(&context);
+    let result = lock_list(&context).and_then(|mut todos| pages::toggle(&mut todos, &id));
+    respond(context, result)
+}
+
+#[middleware_fn]
+async fn get_index(context: Ctx, _next: MiddlewareNext<Ctx>) -> MiddlewareResult<Ctx> {
+    index(context)
+}
+
+#[allow(clippy::result_large_err)]
+fn index(context: Ctx) -> MiddlewareResult<Ctx> {
     let today = Utc::today().naive_utc();
-    for a_todo in all_todos {
-        if a_todo.startable <= today {
-            today_todos.push(a_todo);
-        } else {
-            upcoming_todos.push(a_todo);
-        }
-    }
-    tpl_context.insert("today_todos", &today_todos);
-    tpl_context.insert("upcoming_todos", &upcoming_todos);
+    let templates = &context.extra.templates;
+    let result =
+        lock_list(&context).and_then(|mut todos| pages::index(templates, &mut todos, today));
+    respond(context, result)
+}
 
-    if let Ok(s) = tera.render("index.html.j2", &tpl_context) {
-        req_context.body = Body::from(s);
-    } else {
-        req_context.body = Body::from("template error");
-    }
+#[middleware_fn]
+async fn get_archived(context: Ctx, _next: MiddlewareNext<Ctx>) -> MiddlewareResult<Ctx> {
+    let templates = &context.extra.templates;
+    let result = lock_list(&context).and_then(|mut todos| pages::archived(templates, &mut todos));
+    respond(context, result)
+}
+
+#[middleware_fn]
+async fn post_delete_todo(context: Ctx, _next: MiddlewareNext<Ctx>) -> MiddlewareResult<Ctx> {
+    let id = path_id(&context);
+    let result = lock_list(&context).and_then(|mut todos| pages::delete(&mut todos, &id));
+    respond(context, result)
+}
 
-    Ok(req_context)
+#[middleware_fn]
+async fn post_archive_todo(context: Ctx, _next: MiddlewareNext<Ctx>) -> MiddlewareResult<Ctx> {
+    let id = path_id(&context);
+    let result = lock_list(&context).and_then(|mut todos| pages::archive(&mut todos, &id));
+    respond(context, result)
+}
+
+#[middleware_fn]
+async fn post_unarchive_todo(context: Ctx, _next: MiddlewareNext<Ctx>) -> MiddlewareResult<Ctx> {
+    let id = path_id(&context);
+    let result = lock_list(&context).and_then(|mut todos| pages::unarchive(&mut todos, &id));
+    respond(context, result)
+}
+
+#[middleware_fn]
+async fn get_static(context: Ctx, _next: MiddlewareNext<Ctx>) -> MiddlewareResult<Ctx> {
+    let path = match context.hyper_request {
+        Some(ref req) => req.request.uri().path().to_owned(),
+        None => String::new(),
+    };
+    let file = path.strip_prefix("/static/").unwrap_or("");
+    let result = context.extra.assets.static_file(file);
+    respond(context, result)
 }
 
 #[middleware_fn]
@@ -306,55 +334,94 @@ async fn profiling(mut context: Ctx, next: MiddlewareNext<Ctx>) -> MiddlewareRes
     Ok(context)
 }
 
-fn main() {
-    println!("Starting server...");
-    
-    env_logger::from_env(Env::default().default_filter_or("info")).init();
-    
-    let mut tera = match Tera::new("templates/*.j2") {
-        Ok(t) => t,
+fn listen_address(listen: &str) -> SocketAddr {
+    match listen.parse() {
+        Ok(address) => address,
         Err(e) => {
-            println!("Parsing error(s): {}", e);
+            println!("Can't listen on {}: {}", listen, e);
             ::std::process::exit(1);
         }
-    };
-    tera.autoescape_on(vec!["html.j2"]);
+    }
+}
 
+fn main() {
+    println!("Starting server...");
+    
+    let config = Config::load_or_exit("0.0.0.0:8082");
+    env_logger::Builder::from_env(Env::default().default_filter_or(&config.log)).init();
+    
+    let assets = Assets::new(&config, &EMBEDDED);
+    let feed = Arc::new(Feed::new());
+    let mut todos = open_list(&config);
+    feed.follow(&mut todos);
     let mut app = App::<HyperRequest, Ctx, ServerConfig>::create(
         generate_context,
         ServerConfig {
-            tera: Arc::new(RwLock::new(tera)),
-            todos: Arc::new(RwLock::new(TodoList::new())),
+            templates: Templates::load_or_exit(&config, &assets),
+            todos: Arc::new(RwLock::new(todos)),
+            assets: Arc::new(assets),
+            feed,
+            trust_actor_header: config.trust_actor_header,
         },
     );
 
     // _app.use_middleware("/", profiling);
 
     // app.get("/greeting/:name", vec![greet]);
-    app.set404(async_middleware!(Ctx, [profiling, not_found_404]));
-    // This doesn't appear to work. I asked in the Thruster discord.
-    app.get("/", async_middleware!(Ctx, [profiling, get_index]));
-    // But this one does, so I'm working around it temporarily until I get an answer.
-    app.get("/index.html", async_middleware!(Ctx, [profiling, get_index]));
+    app.get("/", async_middleware!(Ctx, [profiling, error_responses, get_index]));
+    app.post("/new", async_middleware!(Ctx, [profiling, error_responses, post_new_todo]));
+    app.get("/new", async_middleware!(Ctx, [profiling, error_responses, get_new_todo]));
+    app.post("/edit/:id", async_middleware!(Ctx, [profiling, error_responses, post_edit_todo]));
+    app.get("/edit/:id", async_middleware!(Ctx, [profiling, error_responses, get_edit_todo]));
     app.post(
-        "/new",
-        async_middleware!(Ctx, [profiling, query_params, post_new_todo]),
+        "/complete/:id",
+        async_middleware!(Ctx, [profiling, error_responses, post_complete_todo]),
     );
-    app.get("/new", async_middleware!(Ctx, [profiling, get_new_todo]));
+    app.get("/archived", async_middleware!(Ctx, [profiling, error_responses, get_archived]));
+    app.post("/delete/:id", async_middleware!(Ctx, [profiling, error_responses, post_delete_todo]));
     app.post(
-        "/edit",
-        async_middleware!(Ctx, [profiling, query_params, post_edit_todo]),
-    );
-    app.get(
-        "/edit",
-        async_middleware!(Ctx, [profiling, query_params, get_edit_todo]),
+        "/archive/:id",
+        async_middleware!(Ctx, [profiling, error_responses, post_archive_todo]),
     );
     app.post(
-        "/complete",
-        async_middleware!(Ctx, [profiling, query_params, post_complete_todo]),
+        "/unarchive/:id",
+        async_middleware!(Ctx, [profiling, error_responses, post_unarchive_todo]),
     );
-    app.get("/static/*", async_middleware!(Ctx, [file]));
+    app.get("/api/v1/todos", async_middleware!(Ctx, [profiling, error_responses, list_todos]));
+    app.post("/api/v1/todos", async_middleware!(Ctx, [profiling, error_responses, create_todo]));
+    app.get("/api/v1/todos/:id", async_middleware!(Ctx, [profiling, error_responses, get_todo]));
+    app.put(
+        "/api/v1/todos/:id",
+        async_middleware!(Ctx, [profiling, error_responses, replace_todo]),
+    );
+    app.patch(
+        "/api/v1/todos/:id",
+        async_middleware!(Ctx, [profiling, error_responses, patch_todo]),
+    );
+    app.delete(
+        "/api/v1/todos/:id",
+        async_middleware!(Ctx, [profiling, error_responses, delete_todo]),
+    );
+    app.get("/events", async_middleware!(Ctx, [profiling, error_responses, get_events]));
+    app.get("/static/*", async_middleware!(Ctx, [profiling, error_responses, get_static]));
 
+    // The 404 handler is a catch-all, so it has to go in after everything else. It catches "/" no
+    // matter where it goes, which is why it serves the index itself.
+    app.set404(async_middleware!(Ctx, [profiling, error_responses, not_found_404]));
+
+    // `start` would pick its own number of worker threads, so build the runtime here instead.
+    let mut runtime = tokio::runtime::Builder::new_multi_thread();
+    if let Some(workers) = config.workers {
+        runtime.worker_threads(workers);
+    }
+    let runtime = match runtime.enable_all().build() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            println!("Couldn't start the runtime: {}", e);
+            ::std::process::exit(1);
+        }
+    };
     let server = HyperServer::new(app);
-    server.start("0.0.0.0", 8082);
+    let address = listen_address(&config.listen);
+    runtime.block_on(server.build(&address.ip().to_string(), address.port()));
 }
@@ -0,0 +1,176 @@
+//! The JSON API under `/api/v1`, for scripts and other clients that would rather not scrape the
+//! HTML pages. Errors come back as JSON whatever the client accepts.
+
+use crate::response::{parse_id, todo_status, HttpError, Response};
+use crate::revision::{etag, expected_revision};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use todo_list::{IncomingTodo, Todo, TodoError, TodoList, TodoPatch};
+
+/// The same limit actix puts on JSON bodies by default.
+pub const JSON_LIMIT: usize = 32_768;
+
+/// The body of every error response.
+#[derive(Serialize)]
+pub struct ApiError {
+    pub error: String,
+    /// On a 409, the todo as it is now.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current: Option<Todo>,
+}
+
+pub fn error(status: u16, message: impl Into<String>) -> Response {
+    Response::json(
+        status,
+        &ApiError {
+            error: message.into(),
+            current: None,
+        },
+    )
+}
+
+/// For when the todo list's lock has been poisoned.
+pub fn unavailable() -> Response {
+    error(500, "todo list is unavailable")
+}
+
+fn todo_error(e: TodoError) -> Response {
+    let status = todo_status(&e);
+    let message = e.to_string();
+    let current = match e {
+        TodoError::Conflict(current) => Some(current),
+        _ => None,
+    };
+    Response::json(
+        status,
+        &ApiError {
+            error: message,
+            current,
+        },
+    )
+}
+
+fn http_error(e: HttpError) -> Response {
+    error(e.status, e.message)
+}
+
+fn todo_response(status: u16, todo: &Todo) -> Response {
+    Response::json(status, todo).with_header("ETag", etag(todo))
+}
+
+/// Turns away a body that isn't JSON before any of it is read, as actix's `Json` extractor does.
+pub fn expect_json(content_type: Option<&str>) -> Result<(), Response> {
+    let is_json = content_type.is_some_and(|value| {
+        let mime = value
+            .split(';')
+            .next()
+            .unwrap_or("")
+            .trim()
+            .to_ascii_lowercase();
+        mime == "application/json" || mime.ends_with("+json")
+    });
+    if is_json {
+        Ok(())
+    } else {
+        Err(error(400, "Content type error"))
+    }
+}
+
+/// For a body bigger than `JSON_LIMIT`.
+pub fn too_large() -> Response {
+    error(413, "Json payload size is bigger than allowed")
+}
+
+/// Reads a request body as JSON, with the same checks (and error messages) as actix's `Json`
+/// extractor, for servers that don't have one.
+pub fn parse_json<T: DeserializeOwned>(
+    content_type: Option<&str>,
+    body: &[u8],
+) -> Result<T, Response> {
+    expect_json(content_type)?;
+    if body.len() > JSON_LIMIT {
+        return Err(too_large());
+    }
+    serde_json::from_slice(body).map_err(|e| error(400, format!("Json deserialize error: {}", e)))
+}
+
+pub fn list(list: &mut TodoList) -> Response {
+    match list.get_all() {
+        Ok(mut todos) => {
+            todos.sort_by_key(|todo| (todo.startable, todo.due, todo.id));
+            Response::json(200, &todos)
+        }
+        Err(e) => todo_error(e),
+    }
+}
+
+pub fn create(list: &mut TodoList, new: IncomingTodo) -> Response {
+    match list.add(new) {
+        Ok(todo) => {
+            todo_response(201, &todo).with_header("Location", format!("/api/v1/todos/{}", todo.id))
+        }
+        Err(e) => todo_error(e),
+    }
+}
+
+pub fn get(list: &mut TodoList, id: &str) -> Response {
+    let uuid = match parse_id(id) {
+        Ok(uuid) => uuid,
+        Err(e) => return http_error(e),
+    };
+    match list.get(uuid) {
+        Ok(todo) => todo_response(200, &todo),
+        Err(e) => todo_error(e),
+    }
+}
+
+/// PUT: every field has to be there.
+pub fn replace(
+    list: &mut TodoList,
+    id: &str,
+    patch: TodoPatch,
+    if_match: Option<&str>,
+) -> Response {
+    if !patch.is_complete() {
+        return error(
+            400,
+            "PUT needs title, startable, due, complete and archived; use PATCH to change only some",
+        );
+    }
+    update(list, id, patch, if_match)
+}
+
+/// PATCH: only the fields that are there change.
+pub fn update(list: &mut TodoList, id: &str, patch: TodoPatch, if_match: Option<&str>) -> Response {
+    let uuid = match parse_id(id) {
+        Ok(uuid) => uuid,
+        Err(e) => return http_error(e),
+    };
+    let expected = match expected_revision(if_match, patch.revision) {
+        Ok(expected) => expected,
+        Err(e) => return http_error(e),
+    };
+    let existing = match list.get(uuid) {
+        Ok(existing) => existing,
+        Err(e) => return todo_error(e),
+    };
+    let updated = Todo {
+        revision: expected.against(&existing),
+        ..patch.apply(existing)
+    };
+    match list.update(updated) {
+        Ok(todo) => todo_response(200, &todo),
+        Err(e) => todo_error(e),
+    }
+}
+
+pub fn delete(list: &mut TodoList, id: &str) -> Response {
+    let uuid = match parse_id(id) {
+        Ok(uuid) => uuid,
+        Err(e) => return http_error(e),
+    };
+    match list.delete(uuid) {
+        Ok(_) => Response::no_content(),
+        Err(e) => todo_error(e),
+    }
+}
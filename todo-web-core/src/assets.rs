@@ -0,0 +1,132 @@
+//! Templates, and the files served as they are under `/static`. Both are compiled into each server
+//! so that its binary runs from anywhere, unless it's been told to read them from disk.
+
+use crate::config::Config;
+use crate::response::{HttpError, Response};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tera::Tera;
+
+/// What a server compiles into its binary, by file name.
+pub struct Embedded {
+    pub templates: &'static [(&'static str, &'static str)],
+    pub static_files: &'static [(&'static str, &'static [u8])],
+}
+
+/// Where templates and static files come from: the copies built into the server, or for either
+/// kind, a directory on disk so they can be changed without rebuilding.
+pub struct Assets {
+    embedded: &'static Embedded,
+    templates: Option<PathBuf>,
+    static_dir: Option<PathBuf>,
+}
+
+impl Assets {
+    /// The embedded assets, except for any kind the config names a directory for. In dev mode
+    /// both come from disk.
+    pub fn new(config: &Config, embedded: &'static Embedded) -> Assets {
+        let in_dev = |dir: &str| Some(PathBuf::from(dir)).filter(|_| config.dev);
+        Assets {
+            embedded,
+            templates: config.templates.clone().or_else(|| in_dev("templates")),
+            static_dir: config.static_dir.clone().or_else(|| in_dev("static")),
+        }
+    }
+
+    /// Where templates are read from, unless they're the built-in ones.
+    pub fn templates_dir(&self) -> Option<&Path> {
+        self.templates.as_deref()
+    }
+
+    /// Every template, with HTML ones escaped.
+    pub fn templates(&self) -> tera::Result<Tera> {
+        let mut tera = match &self.templates {
+            Some(dir) => Tera::new(&dir.join("*.j2").to_string_lossy())?,
+            None => {
+                let mut tera = Tera::default();
+                tera.add_raw_templates(self.embedded.templates.iter().copied())?;
+                tera
+            }
+        };
+        tera.autoescape_on(vec!["html.j2"]);
+        Ok(tera)
+    }
+
+    /// The file at `path` (what follows `/static/` in the URL). Paths that try to climb out of the
+    /// static directory, or reach hidden files, are treated as missing.
+    pub fn static_file(&self, path: &str) -> Result<Response, HttpError> {
+        let safe = !path.is_empty()
+            && !path.contains('\\')
+            && path
+                .split('/')
+                .all(|segment| !segment.is_empty() && !segment.starts_with('.'));
+        if !safe {
+            return Err(not_found());
+        }
+        let body = match &self.static_dir {
+            Some(dir) => fs::read(dir.join(path)).ok(),
+            None => self
+                .embedded
+                .static_files
+                .iter()
+                .find(|(name, _)| *name == path)
+                .map(|(_, body)| body.to_vec()),
+        };
+        match body {
+            Some(body) => Ok(Response {
+                status: 200,
+                content_type: content_type(path),
+                headers: Vec::new(),
+                body,
+            }),
+            None => Err(not_found()),
+        }
+    }
+}
+
+fn not_found() -> HttpError {
+    HttpError::new(404, "no such file")
+}
+
+fn content_type(path: &str) -> &'static str {
+    match path.rsplit('.').next().unwrap_or("") {
+        "css" => "text/css",
+        "js" => "application/javascript",
+        "html" => "text/html",
+        "json" => "application/json",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "ico" => "image/x-icon",
+        "txt" => "text/plain",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static EMBEDDED: Embedded = Embedded {
+        templates: &[],
+        static_files: &[],
+    };
+
+    #[test]
+    fn a_directory_reads_that_kind_from_disk_without_dev() {
+        let mut config = Config::defaults("127.0.0.1:8080");
+        config.templates = Some(PathBuf::from("elsewhere"));
+        let assets = Assets::new(&config, &EMBEDDED);
+        assert_eq!(assets.templates_dir(), Some(Path::new("elsewhere")));
+        assert_eq!(assets.static_dir, None);
+    }
+
+    #[test]
+    fn dev_reads_everything_from_disk() {
+        let mut config = Config::defaults("127.0.0.1:8080");
+        config.dev = true;
+        config.static_dir = Some(PathBuf::from("public"));
+        let assets = Assets::new(&config, &EMBEDDED);
+        assert_eq!(assets.templates_dir(), Some(Path::new("templates")));
+        assert_eq!(assets.static_dir, Some(PathBuf::from("public")));
+    }
+}
@@ -0,0 +1,423 @@
+//! Settings shared by both servers. Each one comes from, in order of precedence: a command-line
+//! flag, a `TODO_*` environment variable, a TOML file named by `--config` or `TODO_CONFIG`, and
+//! finally the server's default.
+
+use serde::Deserialize;
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+
+pub const USAGE: &str = "\
+Options:
+    --config FILE      read settings from a TOML file            (TODO_CONFIG)
+    --listen ADDR      address and port to listen on             (TODO_LISTEN)
+    --workers N        number of worker threads                  (TODO_WORKERS)
+    --dev              read templates and static files from disk (TODO_DEV)
+                       instead of using the ones built in, and reload
+                       templates whenever they change; --dev=false
+                       turns it off again
+    --templates DIR    read the *.j2 templates from DIR, and     (TODO_TEMPLATES)
+                       reload them as they change; templates
+                       by default with --dev
+    --static DIR       serve DIR under /static; static by        (TODO_STATIC)
+                       default with --dev
+    --data PATH        file to keep todos in; .sqlite or .db for SQLite, .log for an event log,
+                       anything else for JSON. Without one, todos are kept in memory.
+                                                                 (TODO_DATA)
+    --log LEVEL        log filter, such as info or debug         (TODO_LOG)
+    --trust-actor-header                                         (TODO_TRUST_ACTOR_HEADER)
+                       put changes down to whoever the X-Actor
+                       header names rather than the address they
+                       came from; only for behind a proxy that sets it
+    --help             show this
+
+A TOML file can set listen, workers, dev, templates, static, data, log and trust_actor_header.
+Relative paths in it are relative to the file. A lone argument with no flag is taken as --data.";
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub listen: String,
+    /// `None` leaves it up to the framework, which usually means one per CPU.
+    pub workers: Option<usize>,
+    /// Read templates and static files from disk rather than using the copies built into the
+    /// binary, and reload templates as they change.
+    pub dev: bool,
+    /// Where to read templates from instead of using the built-in ones. In dev mode it's
+    /// `templates` unless it's set.
+    pub templates: Option<PathBuf>,
+    /// Likewise for static files, with `static` in dev mode.
+    pub static_dir: Option<PathBuf>,
+    /// `None` keeps todos in memory.
+    pub data: Option<PathBuf>,
+    pub log: String,
+    /// Take the `X-Actor` header's word for who's making a request. Any client can send it, so
+    /// this is off unless a proxy in front of the server sets it; otherwise changes are put down
+    /// to the address they came from.
+    pub trust_actor_header: bool,
+}
+
+/// What a config file can say. Anything it leaves out falls through to the defaults.
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileConfig {
+    listen: Option<String>,
+    workers: Option<usize>,
+    dev: Option<bool>,
+    templates: Option<PathBuf>,
+    #[serde(rename = "static")]
+    static_dir: Option<PathBuf>,
+    data: Option<PathBuf>,
+    log: Option<String>,
+    trust_actor_header: Option<bool>,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    /// `--help` was asked for.
+    Help,
+    UnknownFlag(String),
+    MissingValue(String),
+    BadWorkers(String),
+    /// A switch such as `dev` set to something other than true or false.
+    BadSwitch(String, String),
+    /// A switch such as `--dev` followed by what looks like a value for it, which would otherwise
+    /// be taken as the data file.
+    SwitchValue(String, String),
+    Read(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Help => write!(f, "help requested"),
+            ConfigError::UnknownFlag(flag) => write!(f, "unknown option {}", flag),
+            ConfigError::MissingValue(flag) => write!(f, "{} needs a value", flag),
+            ConfigError::BadWorkers(value) => {
+                write!(f, "workers has to be a positive number, not {:?}", value)
+            }
+            ConfigError::BadSwitch(name, value) => {
+                write!(f, "{} has to be true or false, not {:?}", name, value)
+            }
+            ConfigError::SwitchValue(flag, value) => {
+                write!(f, "{} takes no value; use {}={}", flag, flag, value)
+            }
+            ConfigError::Read(path, e) => write!(f, "couldn't read {}: {}", path.display(), e),
+            ConfigError::Parse(path, e) => write!(f, "couldn't parse {}: {}", path.display(), e),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+impl Config {
+    /// How a server runs when nothing says otherwise: on `listen`, with its built-in templates and
+    /// static files and its todos in memory.
+    pub fn defaults(listen: &str) -> Config {
+        Config {
+            listen: listen.to_owned(),
+            workers: None,
+            dev: false,
+            templates: None,
+            static_dir: None,
+            data: None,
+            log: "info".to_owned(),
+            trust_actor_header: false,
+        }
+    }
+
+    /// Reads the config from this process's arguments and environment. Anything wrong with
+    /// them is fatal, so this prints why along with the usage and exits.
+    pub fn load_or_exit(default_listen: &str) -> Config {
+        let args: Vec<String> = env::args().skip(1).collect();
+        match Config::load(default_listen, &args, |name| env::var(name).ok()) {
+            Ok(config) => config,
+            Err(ConfigError::Help) => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            Err(e) => {
+                println!("{}\n\n{}", e, USAGE);
+                process::exit(2);
+            }
+        }
+    }
+
+    /// Builds a config from command-line arguments (not including the program name) and a way
+    /// of looking up environment variables.
+    pub fn load<F>(default_listen: &str, args: &[String], var: F) -> Result<Config, ConfigError>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let flags = Flags::parse(args)?;
+        let mut config = Config::defaults(default_listen);
+
+        if let Some(path) = flags.get("config").or_else(|| var("TODO_CONFIG")) {
+            config.apply_file(Path::new(&path))?;
+        }
+
+        for (name, value) in Flags::NAMES.iter().filter_map(|name| {
+            let value = flags
+                .get(name)
+                .or_else(|| var(&format!("TODO_{}", name.to_uppercase().replace('-', "_"))));
+            value.map(|value| (*name, value))
+        }) {
+            config.set(name, value)?;
+        }
+        Ok(config)
+    }
+
+    fn apply_file(&mut self, path: &Path) -> Result<(), ConfigError> {
+        let text = fs::read_to_string(path).map_err(|e| ConfigError::Read(path.to_owned(), e))?;
+        let file: FileConfig =
+            toml::from_str(&text).map_err(|e| ConfigError::Parse(path.to_owned(), e))?;
+        let base = path.parent().unwrap_or_else(|| Path::new(""));
+
+        if let Some(listen) = file.listen {
+            self.listen = listen;
+        }
+        if let Some(workers) = file.workers {
+            if workers == 0 {
+                return Err(ConfigError::BadWorkers(workers.to_string()));
+            }
+            self.workers = Some(workers);
+        }
+        if let Some(dev) = file.dev {
+            self.dev = dev;
+        }
+        if let Some(templates) = file.templates {
+            self.templates = Some(base.join(templates));
+        }
+        if let Some(static_dir) = file.static_dir {
+            self.static_dir = Some(base.join(static_dir));
+        }
+        if let Some(data) = file.data {
+            self.data = Some(base.join(data));
+        }
+        if let Some(log) = file.log {
+            self.log = log;
+        }
+        if let Some(trust) = file.trust_actor_header {
+            self.trust_actor_header = trust;
+        }
+        Ok(())
+    }
+
+    fn set(&mut self, name: &str, value: String) -> Result<(), ConfigError> {
+        match name {
+            "listen" => self.listen = value,
+            "workers" => match value.parse() {
+                Ok(workers) if workers > 0 => self.workers = Some(workers),
+                _ => return Err(ConfigError::BadWorkers(value)),
+            },
+            "dev" => self.dev = switch(name, value)?,
+            "templates" => self.templates = Some(PathBuf::from(value)),
+            "static" => self.static_dir = Some(PathBuf::from(value)),
+            "data" => self.data = Some(PathBuf::from(value)),
+            "log" => self.log = value,
+            "trust-actor-header" => self.trust_actor_header = switch(name, value)?,
+            _ => unreachable!("{} isn't in Flags::NAMES", name),
+        }
+        Ok(())
+    }
+}
+
+/// Whether a switch such as `dev` is on, from its flag or environment variable.
+fn switch(name: &str, value: String) -> Result<bool, ConfigError> {
+    match value.as_str() {
+        "true" | "1" => Ok(true),
+        "false" | "0" => Ok(false),
+        _ => Err(ConfigError::BadSwitch(name.to_owned(), value)),
+    }
+}
+
+/// Whether `value` is one of the ways of saying whether a switch is on, as `switch` takes them.
+fn is_switch_value(value: &str) -> bool {
+    matches!(value, "true" | "1" | "false" | "0")
+}
+
+/// The `--name value` pairs from the command line.
+struct Flags {
+    values: Vec<(String, String)>,
+}
+
+impl Flags {
+    /// Every setting, as it's spelled in a flag. Its environment variable is the same in upper
+    /// case with `TODO_` in front and underscores for hyphens.
+    const NAMES: [&'static str; 8] = [
+        "listen",
+        "workers",
+        "dev",
+        "templates",
+        "static",
+        "data",
+        "log",
+        "trust-actor-header",
+    ];
+
+    /// Settings that are on when their flag is given alone.
+    const SWITCHES: [&'static str; 2] = ["dev", "trust-actor-header"];
+
+    fn parse(args: &[String]) -> Result<Flags, ConfigError> {
+        let mut values = Vec::new();
+        let mut args = args.iter().peekable();
+        while let Some(arg) = args.next() {
+            if arg == "--help" || arg == "-h" {
+                return Err(ConfigError::Help);
+            }
+            let name = match arg.strip_prefix("--") {
+                Some(name) => name,
+                // Before there were flags, the data file was the only argument.
+                None => {
+                    values.push(("data".to_owned(), arg.clone()));
+                    continue;
+                }
+            };
+            // Both `--name value` and `--name=value` work, and a switch such as `--dev` alone turns
+            // it on.
+            let (name, value) = match name.split_once('=') {
+                Some((name, value)) => (name, value.to_owned()),
+                None if Flags::SWITCHES.contains(&name) => {
+                    // Otherwise `--dev false` turns dev mode on and keeps todos in `./false`.
+                    if let Some(next) = args.next_if(|next| is_switch_value(next)) {
+                        return Err(ConfigError::SwitchValue(arg.clone(), next.clone()));
+                    }
+                    (name, "true".to_owned())
+                }
+                None => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| ConfigError::MissingValue(arg.clone()))?;
+                    (name, value.clone())
+                }
+            };
+            if name != "config" && !Flags::NAMES.contains(&name) {
+                return Err(ConfigError::UnknownFlag(format!("--{}", name)));
+            }
+            values.push((name.to_owned(), value));
+        }
+        Ok(Flags { values })
+    }
+
+    /// The last value given for `name`, so later flags override earlier ones.
+    fn get(&self, name: &str) -> Option<String> {
+        self.values
+            .iter()
+            .rev()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(args: &[&str]) -> Result<Config, ConfigError> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        Config::load("127.0.0.1:8080", &args, |_| None)
+    }
+
+    /// Writes `contents` to a config file in a directory of the test's own, which the test removes
+    /// when it's done.
+    fn config_file(name: &str, contents: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("todo-config-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("todo.toml");
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn dev_is_a_switch() {
+        assert!(load(&["--dev"]).unwrap().dev);
+        assert!(!load(&["--dev=false"]).unwrap().dev);
+        assert!(!load(&["--dev", "--dev=0"]).unwrap().dev);
+    }
+
+    #[test]
+    fn dev_followed_by_a_value_is_rejected() {
+        for value in &["false", "true", "0", "1"] {
+            match load(&["--dev", value]) {
+                Err(ConfigError::SwitchValue(flag, v)) => {
+                    assert_eq!(flag, "--dev");
+                    assert_eq!(v, *value);
+                }
+                other => panic!("--dev {}: {:?}", value, other),
+            }
+        }
+    }
+
+    #[test]
+    fn dev_can_still_come_before_the_data_file() {
+        let config = load(&["--dev", "todos.json"]).unwrap();
+        assert!(config.dev);
+        assert_eq!(config.data, Some(PathBuf::from("todos.json")));
+    }
+
+    #[test]
+    fn flags_beat_the_environment_which_beats_the_file_which_beats_the_default() {
+        let path = config_file("precedence", "listen = \"file:1\"\nworkers = 2\nlog = \"warn\"\n");
+        let path = path.to_str().unwrap().to_owned();
+        let var = |name: &str| match name {
+            "TODO_CONFIG" => Some(path.clone()),
+            "TODO_LISTEN" => Some("env:1".to_owned()),
+            "TODO_WORKERS" => Some("3".to_owned()),
+            _ => None,
+        };
+        let args = vec!["--listen".to_owned(), "flag:1".to_owned()];
+        let config = Config::load("default:1", &args, var).unwrap();
+        assert_eq!(config.listen, "flag:1");
+        assert_eq!(config.workers, Some(3));
+        assert_eq!(config.log, "warn");
+        assert_eq!(config.data, None);
+        fs::remove_dir_all(Path::new(&path).parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn relative_paths_in_a_file_are_relative_to_it() {
+        let path = config_file(
+            "relative",
+            "templates = \"views\"\nstatic = \"public\"\ndata = \"todos.db\"\n",
+        );
+        let dir = path.parent().unwrap();
+        let config = load(&["--config", path.to_str().unwrap()]).unwrap();
+        assert_eq!(config.templates, Some(dir.join("views")));
+        assert_eq!(config.static_dir, Some(dir.join("public")));
+        assert_eq!(config.data, Some(dir.join("todos.db")));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn a_file_with_an_unknown_key_is_rejected() {
+        let path = config_file("unknown", "listen = \"127.0.0.1:1\"\nport = 8080\n");
+        match load(&["--config", path.to_str().unwrap()]) {
+            Err(ConfigError::Parse(p, e)) => {
+                assert_eq!(p, path);
+                assert!(e.to_string().contains("port"), "{}", e);
+            }
+            other => panic!("{:?}", other),
+        }
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn the_actor_header_is_only_trusted_when_asked() {
+        assert!(!load(&[]).unwrap().trust_actor_header);
+        assert!(load(&["--trust-actor-header"]).unwrap().trust_actor_header);
+        let var = |name: &str| match name {
+            "TODO_TRUST_ACTOR_HEADER" => Some("1".to_owned()),
+            _ => None,
+        };
+        assert!(Config::load("127.0.0.1:8080", &[], var).unwrap().trust_actor_header);
+        let path = config_file("trust", "trust_actor_header = true\n");
+        let config = load(&["--config", path.to_str().unwrap()]).unwrap();
+        assert!(config.trust_actor_header);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,240 @@
+//! Changes to the todo list, passed on as they happen to every client following along.
+
+use futures::channel::mpsc::{self, Receiver, Sender};
+use log::error;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use todo_list::{TodoChange, TodoList};
+
+/// How many of the latest changes are kept for clients catching up on what they missed, and how
+/// far behind a client can fall before it's dropped. Since it's the same number, a client that's
+/// dropped can always catch up again by reconnecting.
+pub const FEED_HISTORY: usize = 256;
+
+/// One change, numbered in the order it happened.
+#[derive(Clone, Debug)]
+pub struct FeedEvent {
+    pub id: u64,
+    /// The `TodoChange` as JSON.
+    pub data: String,
+}
+
+pub struct Feed {
+    /// When this feed started, so an event id handed out before a restart can be told apart from
+    /// one handed out since, even though the numbering starts again from 1.
+    epoch: u128,
+    state: Mutex<FeedState>,
+}
+
+#[derive(Default)]
+struct FeedState {
+    last_id: u64,
+    /// The last `FEED_HISTORY` changes, oldest first.
+    recent: VecDeque<FeedEvent>,
+    subscribers: Vec<Sender<FeedEvent>>,
+}
+
+/// A client picking up where it left off.
+pub struct Resumed {
+    /// The changes it missed, oldest first.
+    pub missed: Vec<FeedEvent>,
+    /// Whether some of what it missed has already been forgotten, or it was following the feed
+    /// from before the server restarted. Either way, it'll have to start over from the list as it
+    /// is now.
+    pub gap: bool,
+    /// The number of the latest change so far, which `events` carry on from.
+    pub last_id: u64,
+    /// Every change from now on, until it falls too far behind.
+    pub events: Receiver<FeedEvent>,
+}
+
+impl Default for Feed {
+    fn default() -> Feed {
+        Feed::new()
+    }
+}
+
+impl Feed {
+    pub fn new() -> Feed {
+        let epoch = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_nanos());
+        Feed::with_epoch(epoch)
+    }
+
+    fn with_epoch(epoch: u128) -> Feed {
+        Feed {
+            epoch,
+            state: Mutex::default(),
+        }
+    }
+
+    /// The id to send a client along with event number `id`, such as in an SSE `id:` field, for
+    /// it to hand back to `resume`.
+    pub fn event_id(&self, id: u64) -> String {
+        format!("{}-{}", self.epoch, id)
+    }
+
+    /// The event number in an id from `event_id`, if it came from this feed rather than one from
+    /// before a restart.
+    fn parse_event_id(&self, id: &str) -> Option<u64> {
+        let (epoch, id) = id.trim().split_once('-')?;
+        if epoch.parse() != Ok(self.epoch) {
+            return None;
+        }
+        id.parse().ok()
+    }
+
+    /// Publishes every change made to `list` from now on.
+    pub fn follow(self: &Arc<Feed>, list: &mut TodoList) {
+        let feed = Arc::clone(self);
+        list.watch(move |change| feed.publish(change));
+    }
+
+    pub fn publish(&self, change: &TodoChange) {
+        let data = match serde_json::to_string(change) {
+            Ok(data) => data,
+            Err(e) => {
+                error!("couldn't serialize {:?}: {}", change, e);
+                return;
+            }
+        };
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => return,
+        };
+        state.last_id += 1;
+        let event = FeedEvent {
+            id: state.last_id,
+            data,
+        };
+        if state.recent.len() == FEED_HISTORY {
+            state.recent.pop_front();
+        }
+        state.recent.push_back(event.clone());
+        // Whoever's gone has dropped their receiver, so this is where they're forgotten, along with
+        // anyone who's stopped reading. Their stream ends once they've read what they were sent.
+        state
+            .subscribers
+            .retain_mut(|subscriber| subscriber.try_send(event.clone()).is_ok());
+    }
+
+    /// Every change published from now on, until it falls `FEED_HISTORY` behind.
+    pub fn subscribe(&self) -> Receiver<FeedEvent> {
+        let (sender, receiver) = mpsc::channel(FEED_HISTORY);
+        if let Ok(mut state) = self.state.lock() {
+            state.subscribers.push(sender);
+        }
+        receiver
+    }
+
+    /// Every change after the one `last_event_id` names that's still remembered, then every
+    /// change from now on. Without a `last_event_id`, only the changes from now on. One that
+    /// `event_id` didn't hand out since this feed started is a gap.
+    pub fn resume(&self, last_event_id: Option<&str>) -> Resumed {
+        let (sender, events) = mpsc::channel(FEED_HISTORY);
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => {
+                return Resumed {
+                    missed: Vec::new(),
+                    gap: true,
+                    last_id: 0,
+                    events,
+                }
+            }
+        };
+        let oldest = state
+            .recent
+            .front()
+            .map_or(state.last_id + 1, |event| event.id);
+        let after = match last_event_id {
+            Some(id) => self.parse_event_id(id),
+            None => Some(state.last_id),
+        };
+        let missed = match after {
+            Some(after) if after <= state.last_id && after + 1 >= oldest => {
+                let missed = state.recent.iter().filter(|event| event.id > after);
+                Some(missed.cloned().collect())
+            }
+            _ => None,
+        };
+        let gap = missed.is_none();
+        let missed = missed.unwrap_or_default();
+        // Subscribing while still holding the lock means nothing published in between is lost.
+        state.subscribers.push(sender);
+        Resumed {
+            missed,
+            gap,
+            last_id: state.last_id,
+            events,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on_stream;
+    use uuid::Uuid;
+
+    fn publish(feed: &Feed, changes: usize) {
+        for _ in 0..changes {
+            feed.publish(&TodoChange::Deleted { id: Uuid::new_v4() });
+        }
+    }
+
+    #[test]
+    fn drops_a_subscriber_that_falls_too_far_behind() {
+        let feed = Feed::new();
+        let events = feed.subscribe();
+        publish(&feed, FEED_HISTORY * 2);
+        let read: Vec<u64> = block_on_stream(events).map(|event| event.id).collect();
+        assert!(read.len() >= FEED_HISTORY && read.len() < FEED_HISTORY * 2);
+        assert_eq!(read, (1..=read.len() as u64).collect::<Vec<_>>());
+        assert!(feed.state.lock().unwrap().subscribers.is_empty());
+    }
+
+    #[test]
+    fn a_dropped_subscriber_can_catch_up_by_resuming() {
+        let feed = Feed::new();
+        let events = feed.subscribe();
+        publish(&feed, FEED_HISTORY + 10);
+        let last_read = block_on_stream(events).last().unwrap().id;
+
+        let resumed = feed.resume(Some(&feed.event_id(last_read)));
+        assert!(!resumed.gap);
+        let missed: Vec<u64> = resumed.missed.iter().map(|event| event.id).collect();
+        assert_eq!(
+            missed,
+            (last_read + 1..=FEED_HISTORY as u64 + 10).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn an_id_from_before_a_restart_is_a_gap() {
+        let before = Feed::with_epoch(1);
+        publish(&before, 5);
+        let last_seen = before.event_id(5);
+
+        // The new process has already got further than the client had.
+        let after = Feed::with_epoch(2);
+        publish(&after, 7);
+        let resumed = after.resume(Some(&last_seen));
+        assert!(resumed.gap);
+        assert!(resumed.missed.is_empty());
+        assert_eq!(resumed.last_id, 7);
+        assert!(!after.resume(Some(&after.event_id(5))).gap);
+    }
+
+    #[test]
+    fn an_id_that_was_never_handed_out_is_a_gap() {
+        let feed = Feed::with_epoch(1);
+        publish(&feed, 3);
+        assert!(feed.resume(Some("3")).gap);
+        assert!(feed.resume(Some("1-x")).gap);
+        assert!(feed.resume(Some(&feed.event_id(4))).gap);
+        assert!(!feed.resume(None).gap);
+    }
+}
@@ -0,0 +1,250 @@
+//! Decoding for `application/x-www-form-urlencoded` bodies, following the WHATWG URL spec's
+//! parser: `+` is a space, `%XX` is a byte, and a `%` that isn't followed by two hex digits is
+//! just a `%`.
+
+use std::fmt;
+use todo_list::TodoForm;
+
+/// Bigger bodies than this are turned away before any decoding happens.
+pub const MAX_FORM_BYTES: usize = 64 * 1024;
+/// Likewise for bodies with more fields than this.
+pub const MAX_FIELDS: usize = 64;
+
+#[derive(Debug)]
+pub enum FormError {
+    TooLarge,
+    TooManyFields,
+    /// A name or value didn't decode to valid UTF-8.
+    InvalidUtf8,
+    /// A field that should appear once was sent more than once.
+    Repeated(String),
+}
+
+impl fmt::Display for FormError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FormError::TooLarge => write!(f, "form is larger than {} bytes", MAX_FORM_BYTES),
+            FormError::TooManyFields => write!(f, "form has more than {} fields", MAX_FIELDS),
+            FormError::InvalidUtf8 => write!(f, "form is not valid UTF-8"),
+            FormError::Repeated(name) => write!(f, "form field {} was sent more than once", name),
+        }
+    }
+}
+
+impl std::error::Error for FormError {}
+
+/// A decoded form, keeping every name/value pair in the order it was sent.
+pub struct Form {
+    pairs: Vec<(String, String)>,
+}
+
+impl Form {
+    pub fn parse(body: &[u8]) -> Result<Form, FormError> {
+        if body.len() > MAX_FORM_BYTES {
+            return Err(FormError::TooLarge);
+        }
+        let mut pairs = Vec::new();
+        for piece in body.split(|&b| b == b'&').filter(|piece| !piece.is_empty()) {
+            if pairs.len() == MAX_FIELDS {
+                return Err(FormError::TooManyFields);
+            }
+            // A piece without an `=` is a name with an empty value.
+            let (name, value) = match piece.iter().position(|&b| b == b'=') {
+                Some(i) => (&piece[..i], &piece[i + 1..]),
+                None => (piece, &[][..]),
+            };
+            pairs.push((decode(name)?, decode(value)?));
+        }
+        Ok(Form { pairs })
+    }
+
+    /// The value of a field that's expected at most once.
+    pub fn single(&self, name: &str) -> Result<Option<&str>, FormError> {
+        let mut values = self
+            .pairs
+            .iter()
+            .filter(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str());
+        let first = values.next();
+        if values.next().is_some() {
+            return Err(FormError::Repeated(name.to_owned()));
+        }
+        Ok(first)
+    }
+}
+
+/// Pulls the todo fields out of a submitted form. Missing fields come through empty, so they're
+/// reported the same way as any other bad input.
+pub fn todo_form(body: &[u8]) -> Result<TodoForm, FormError> {
+    let form = Form::parse(body)?;
+    let field = |name: &str| -> Result<String, FormError> {
+        Ok(form.single(name)?.unwrap_or_default().to_owned())
+    };
+    Ok(TodoForm {
+        title: field("title")?,
+        startable: field("start-date")?,
+        due: field("due-date")?,
+        revision: field("revision")?,
+    })
+}
+
+fn decode(encoded: &[u8]) -> Result<String, FormError> {
+    let mut decoded = Vec::with_capacity(encoded.len());
+    let mut i = 0;
+    while i < encoded.len() {
+        match encoded[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if i + 2 < encoded.len() && is_hex_pair(&encoded[i + 1..i + 3]) => {
+                decoded.push(hex_value(encoded[i + 1]) << 4 | hex_value(encoded[i + 2]));
+                i += 2;
+            }
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8(decoded).map_err(|_| FormError::InvalidUtf8)
+}
+
+fn is_hex_pair(pair: &[u8]) -> bool {
+    pair.iter().all(u8::is_ascii_hexdigit)
+}
+
+fn hex_value(digit: u8) -> u8 {
+    match digit {
+        b'0'..=b'9' => digit - b'0',
+        b'a'..=b'f' => digit - b'a' + 10,
+        _ => digit - b'A' + 10,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn value(body: &str, name: &str) -> Result<Option<String>, FormError> {
+        let form = Form::parse(body.as_bytes())?;
+        Ok(form.single(name)?.map(str::to_owned))
+    }
+
+    /// The body of one of the captured requests fed to the fuzzer, as much of it as its
+    /// `Content-Length` says there is, which is what a server would read.
+    fn fuzz_body(request: &[u8]) -> &[u8] {
+        let text = std::str::from_utf8(request).unwrap();
+        let (head, body) = text.split_once("\n\n").unwrap();
+        let length = head
+            .lines()
+            .find_map(|line| line.strip_prefix("Content-Length: "))
+            .unwrap()
+            .trim()
+            .parse()
+            .unwrap();
+        &body.as_bytes()[..length]
+    }
+
+    #[test]
+    fn plus_is_a_space() {
+        assert_eq!(value("title=a+b++c", "title").unwrap().unwrap(), "a b  c");
+        assert_eq!(value("title=a%2Bb", "title").unwrap().unwrap(), "a+b");
+    }
+
+    #[test]
+    fn percent_is_only_an_escape_before_two_hex_digits() {
+        assert_eq!(value("title=100%25", "title").unwrap().unwrap(), "100%");
+        assert_eq!(value("title=100%", "title").unwrap().unwrap(), "100%");
+        assert_eq!(value("title=100%2", "title").unwrap().unwrap(), "100%2");
+        assert_eq!(
+            value("title=%zzpasta", "title").unwrap().unwrap(),
+            "%zzpasta"
+        );
+        assert_eq!(
+            value("title=%e2%9C%93", "title").unwrap().unwrap(),
+            "\u{2713}"
+        );
+    }
+
+    #[test]
+    fn rejects_bytes_that_arent_utf8() {
+        assert!(matches!(
+            Form::parse(b"title=%ff"),
+            Err(FormError::InvalidUtf8)
+        ));
+        assert!(matches!(Form::parse(b"%ff=x"), Err(FormError::InvalidUtf8)));
+    }
+
+    #[test]
+    fn a_name_without_a_value_is_empty() {
+        assert_eq!(value("done&title=x", "done").unwrap().unwrap(), "");
+        assert_eq!(value("done=&title=x", "done").unwrap().unwrap(), "");
+        assert_eq!(value("&&title=x&&", "title").unwrap().unwrap(), "x");
+        assert_eq!(value("title=x", "done").unwrap(), None);
+    }
+
+    #[test]
+    fn repeated_names_are_rejected_where_one_is_expected() {
+        assert!(matches!(
+            value("title=a&title=b", "title"),
+            Err(FormError::Repeated(name)) if name == "title"
+        ));
+        assert!(matches!(
+            todo_form(b"title=a&start-date=2021-08-02&start-date=2021-08-03"),
+            Err(FormError::Repeated(name)) if name == "start-date"
+        ));
+        // Other fields can repeat as much as they like.
+        assert_eq!(value("x=1&x=2&title=a", "title").unwrap().unwrap(), "a");
+    }
+
+    #[test]
+    fn limits_the_number_of_fields() {
+        let fields = |n: usize| vec!["x=1"; n].join("&");
+        assert!(Form::parse(fields(MAX_FIELDS).as_bytes()).is_ok());
+        assert!(matches!(
+            Form::parse(fields(MAX_FIELDS + 1).as_bytes()),
+            Err(FormError::TooManyFields)
+        ));
+    }
+
+    #[test]
+    fn limits_the_size_of_the_body() {
+        let body = |n: usize| format!("title={}", "a".repeat(n - "title=".len()));
+        assert!(Form::parse(body(MAX_FORM_BYTES).as_bytes()).is_ok());
+        assert!(matches!(
+            Form::parse(body(MAX_FORM_BYTES + 1).as_bytes()),
+            Err(FormError::TooLarge)
+        ));
+    }
+
+    #[test]
+    fn decodes_the_fuzzers_captured_requests() {
+        let new = todo_form(fuzz_body(include_bytes!(
+            "../../thruster-fuzz-requests/post-new"
+        )))
+        .unwrap();
+        assert_eq!(new.title, "iatetoomuchpastaa");
+        assert_eq!(new.startable, "2021-08-02");
+        assert_eq!(new.due, "2021-08-04");
+
+        let edit = todo_form(fuzz_body(include_bytes!(
+            "../../thruster-fuzz-requests/post-edit"
+        )))
+        .unwrap();
+        assert_eq!(edit.title, "dingbats but with %SPECIAL%CHARACTERS!%");
+        assert_eq!(edit.startable, "1970-01-01");
+        assert_eq!(edit.due, "9999-12-31");
+        assert_eq!(edit.revision, "");
+    }
+
+    proptest! {
+        #[test]
+        fn decodes_whatever_a_browser_would_encode(name: String, text: String) {
+            let body = format!(
+                "{}={}",
+                form_urlencoded::byte_serialize(name.as_bytes()).collect::<String>(),
+                form_urlencoded::byte_serialize(text.as_bytes()).collect::<String>(),
+            );
+            prop_assume!(body.len() <= MAX_FORM_BYTES);
+            let form = Form::parse(body.as_bytes()).unwrap();
+            prop_assert_eq!(form.pairs, vec![(name, text)]);
+        }
+    }
+}
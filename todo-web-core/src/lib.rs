@@ -0,0 +1,26 @@
+//! Everything the todo web servers do that doesn't depend on which framework is doing the
+//! serving: decoding forms, deciding what each page shows, rendering it, and picking status codes.
+//! The servers only pull the pieces they need out of each request, call in here, and send back
+//! the `Response` they get.
+
+pub mod api;
+mod assets;
+mod config;
+mod feed;
+mod form;
+mod list;
+pub mod pages;
+mod response;
+mod revision;
+mod templates;
+mod view;
+
+pub use assets::{Assets, Embedded};
+pub use config::{Config, ConfigError, USAGE};
+pub use feed::{Feed, FeedEvent, Resumed, FEED_HISTORY};
+pub use form::{todo_form, Form, FormError, MAX_FIELDS, MAX_FORM_BYTES};
+pub use list::{actor, open_list, ACTOR_HEADER};
+pub use pages::PageResult;
+pub use response::{accepts_json, HttpError, Response};
+pub use templates::Templates;
+pub use view::{ArchivedView, DetailView, IndexView};
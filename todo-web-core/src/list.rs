@@ -0,0 +1,80 @@
+//! Opening the todo list the config points at, and saying who's changing it.
+
+use crate::config::Config;
+use std::net::IpAddr;
+use std::path::Path;
+use todo_list::{EventLogStore, JsonFileStore, MemoryStore, SqliteStore, StoreError, TodoList};
+
+/// Keeps todos in the configured data file, if there is one, and in memory otherwise. Files
+/// ending in `.sqlite` or `.db` are SQLite databases, files ending in `.log` are event logs, and
+/// anything else is JSON. If the file can't be opened, the server has nothing to serve, so this
+/// exits.
+pub fn open_list(config: &Config) -> TodoList {
+    match config.data.as_deref() {
+        Some(path) => match open_store(path) {
+            Ok(list) => list,
+            Err(e) => {
+                println!("Couldn't load todos from {}: {}", path.display(), e);
+                ::std::process::exit(1);
+            }
+        },
+        None => TodoList::with_store(MemoryStore::new()),
+    }
+}
+
+fn open_store(path: &Path) -> Result<TodoList, StoreError> {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("sqlite") | Some("db") => Ok(TodoList::with_store(SqliteStore::open(path)?)),
+        Some("log") => Ok(TodoList::with_store(EventLogStore::open(path)?)),
+        _ => Ok(TodoList::with_store(JsonFileStore::open(path)?)),
+    }
+}
+
+/// The header a proxy in front of the server can name whoever's logged in with. It's only
+/// believed with `Config::trust_actor_header`, since any client can send it.
+pub const ACTOR_HEADER: &str = "X-Actor";
+
+/// Who a request's changes get put down to in stores that keep an audit trail: whoever it names in
+/// `X-Actor` if `trust_header` is set, or failing that the address it came from. Servers pass this
+/// to `TodoList::set_actor` on every request, so one that says nothing isn't taken for the last
+/// one.
+pub fn actor(trust_header: bool, named: Option<&str>, peer: Option<IpAddr>) -> Option<String> {
+    let named = named
+        .filter(|_| trust_header)
+        .map(str::trim)
+        .filter(|name| !name.is_empty());
+    match named {
+        Some(name) => Some(name.to_owned()),
+        None => peer.map(|ip| ip.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    const PEER: Option<IpAddr> = Some(IpAddr::V4(Ipv4Addr::LOCALHOST));
+
+    #[test]
+    fn a_trusted_header_is_trimmed() {
+        assert_eq!(actor(true, Some("  alice \t"), PEER).as_deref(), Some("alice"));
+    }
+
+    #[test]
+    fn an_empty_header_falls_back_to_the_peer() {
+        assert_eq!(actor(true, Some("   "), PEER).as_deref(), Some("127.0.0.1"));
+    }
+
+    #[test]
+    fn a_missing_header_falls_back_to_the_peer() {
+        assert_eq!(actor(true, None, PEER).as_deref(), Some("127.0.0.1"));
+        assert_eq!(actor(true, None, None), None);
+    }
+
+    #[test]
+    fn the_header_is_ignored_unless_trusted() {
+        assert_eq!(actor(false, Some("alice"), PEER).as_deref(), Some("127.0.0.1"));
+        assert_eq!(actor(false, Some("alice"), None), None);
+    }
+}
@@ -0,0 +1,169 @@
+//! The HTML pages. Each function takes what the request carried, already pulled out by whichever
+//! framework is serving it, and gives back the whole response.
+
+use crate::form::todo_form;
+use crate::response::{parse_id, HttpError, Response};
+use crate::revision::{etag, expected_revision};
+use crate::templates::Templates;
+use crate::view::{ArchivedView, DetailView, IndexView};
+use chrono::NaiveDate;
+use serde::Serialize;
+use todo_list::{Todo, TodoError, TodoForm, TodoList};
+
+pub type PageResult = Result<Response, HttpError>;
+
+/// Renders `view` with `template` into a page with the given status.
+pub fn render<V: Serialize>(
+    templates: &Templates,
+    template: &str,
+    view: &V,
+    status: u16,
+) -> PageResult {
+    let body = templates.render(template, view)?;
+    Ok(Response::html(status, body))
+}
+
+pub fn index(templates: &Templates, list: &mut TodoList, today: NaiveDate) -> PageResult {
+    let view = IndexView::new(list.get_all()?, today);
+    render(templates, "index.html.j2", &view, 200)
+}
+
+pub fn archived(templates: &Templates, list: &mut TodoList) -> PageResult {
+    let view = ArchivedView {
+        archived_todos: list.get_archived()?,
+    };
+    render(templates, "archived.html.j2", &view, 200)
+}
+
+pub fn new_todo(templates: &Templates) -> PageResult {
+    let view: DetailView<Todo> = DetailView {
+        action: "Create",
+        todo: None,
+        errors: None,
+        current: None,
+    };
+    render(templates, "detail.html.j2", &view, 200)
+}
+
+pub fn edit_todo(templates: &Templates, list: &mut TodoList, id: &str) -> PageResult {
+    let todo = list.get(parse_id(id)?)?;
+    let view = DetailView {
+        action: "Update",
+        todo: Some(&todo),
+        errors: None,
+        current: None,
+    };
+    let response = render(templates, "detail.html.j2", &view, 200)?;
+    Ok(response.with_header("ETag", etag(&todo)))
+}
+
+pub fn create(templates: &Templates, list: &mut TodoList, body: &[u8]) -> PageResult {
+    let form = todo_form(body)?;
+    let new = match form.parse(list.limits()) {
+        Ok(new) => new,
+        Err(errors) => return invalid_form(templates, "Create", &form, &errors),
+    };
+    list.add(new)?;
+    Ok(Response::redirect("/"))
+}
+
+/// `if_match` is the request's `If-Match` header, which takes precedence over the revision in
+/// the form.
+pub fn update(
+    templates: &Templates,
+    list: &mut TodoList,
+    id: &str,
+    body: &[u8],
+    if_match: Option<&str>,
+) -> PageResult {
+    let form = todo_form(body)?;
+    let existing = list.get(parse_id(id)?)?;
+    let edited = match form.parse(list.limits()) {
+        Ok(edited) => edited,
+        Err(errors) => return invalid_form(templates, "Update", &form, &errors),
+    };
+    let expected = expected_revision(if_match, sent_revision(&form)?)?;
+
+    let updated = Todo {
+        id: existing.id,
+        title: edited.title,
+        complete: existing.complete,
+        startable: edited.startable,
+        due: edited.due,
+        archived: existing.archived,
+        revision: expected.against(&existing),
+    };
+    match list.update(updated) {
+        Ok(_) => Ok(Response::redirect("/")),
+        Err(TodoError::Conflict(current)) => conflict_form(templates, &form, &current),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// What the checkboxes on the front page call.
+pub fn toggle(list: &mut TodoList, id: &str) -> PageResult {
+    list.toggle_completed(parse_id(id)?)?;
+    Ok(Response::json(200, &serde_json::json!({})))
+}
+
+pub fn delete(list: &mut TodoList, id: &str) -> PageResult {
+    let deleted = list.delete(parse_id(id)?)?;
+    Ok(Response::redirect(if deleted.archived {
+        "/archived"
+    } else {
+        "/"
+    }))
+}
+
+pub fn archive(list: &mut TodoList, id: &str) -> PageResult {
+    list.archive(parse_id(id)?)?;
+    Ok(Response::redirect("/"))
+}
+
+pub fn unarchive(list: &mut TodoList, id: &str) -> PageResult {
+    list.unarchive(parse_id(id)?)?;
+    Ok(Response::redirect("/archived"))
+}
+
+/// The revision the form was filled in from. A blank one wasn't sent, but one that isn't a number
+/// is as much a bad request as a malformed `If-Match`.
+fn sent_revision(form: &TodoForm) -> Result<Option<u64>, HttpError> {
+    match form.revision.trim() {
+        "" => Ok(None),
+        revision => revision.parse().map(Some).map_err(|_| {
+            HttpError::new(400, format!("revision should be a number, not {:?}", revision))
+        }),
+    }
+}
+
+/// Shows the todo form again with what was typed into it and what was wrong with it.
+fn invalid_form(
+    templates: &Templates,
+    action: &str,
+    form: &TodoForm,
+    errors: &todo_list::ValidationErrors,
+) -> PageResult {
+    let view = DetailView {
+        action,
+        todo: Some(form),
+        errors: Some(errors),
+        current: None,
+    };
+    render(templates, "detail.html.j2", &view, 400)
+}
+
+/// Shows the todo form again, keeping what was typed into it, alongside the newer version of the
+/// todo that it would overwrite. Submitting it again replaces that version.
+fn conflict_form(templates: &Templates, form: &TodoForm, current: &Todo) -> PageResult {
+    let retry = TodoForm {
+        revision: current.revision.to_string(),
+        ..form.clone()
+    };
+    let view = DetailView {
+        action: "Update",
+        todo: Some(&retry),
+        errors: None,
+        current: Some(current),
+    };
+    render(templates, "detail.html.j2", &view, 409)
+}
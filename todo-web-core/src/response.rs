@@ -0,0 +1,160 @@
+use crate::form::FormError;
+use serde::Serialize;
+use std::fmt;
+use todo_list::TodoError;
+use uuid::Uuid;
+
+/// Everything a server needs to send back, without saying anything about how it gets sent.
+#[derive(Debug)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    /// Headers beyond `Content-Type`, such as `Location` and `ETag`.
+    pub headers: Vec<(&'static str, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn html(status: u16, body: String) -> Response {
+        Response {
+            status,
+            content_type: "text/html",
+            headers: Vec::new(),
+            body: body.into_bytes(),
+        }
+    }
+
+    pub fn json<T: Serialize>(status: u16, value: &T) -> Response {
+        match serde_json::to_string(value) {
+            Ok(body) => Response {
+                status,
+                content_type: "application/json",
+                headers: Vec::new(),
+                body: body.into_bytes(),
+            },
+            Err(_) => Response {
+                status: 500,
+                content_type: "application/json",
+                headers: Vec::new(),
+                body: b"{\"error\":\"couldn't serialize the response\"}".to_vec(),
+            },
+        }
+    }
+
+    pub fn redirect(to: &str) -> Response {
+        Response {
+            status: 302,
+            content_type: "text/plain",
+            headers: vec![("Location", to.to_owned())],
+            body: Vec::new(),
+        }
+    }
+
+    pub fn no_content() -> Response {
+        Response {
+            status: 204,
+            content_type: "text/plain",
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    pub fn with_header(mut self, name: &'static str, value: String) -> Response {
+        self.headers.push((name, value));
+        self
+    }
+}
+
+/// Why a page couldn't be served. Servers turn this into a response with `into_response` once
+/// they know what the client accepts.
+#[derive(Debug)]
+pub struct HttpError {
+    pub status: u16,
+    pub message: String,
+}
+
+impl HttpError {
+    pub fn new(status: u16, message: impl Into<String>) -> HttpError {
+        HttpError {
+            status,
+            message: message.into(),
+        }
+    }
+
+    /// A lock left poisoned by a request that panicked while holding it. Whatever it guards may
+    /// be half-changed, so nothing more should be done with it.
+    pub fn poisoned() -> HttpError {
+        HttpError::new(500, "server state is unavailable")
+    }
+
+    /// Clients asking for JSON get `{"error": ...}`; everyone else gets a page.
+    pub fn into_response(self, accept: Option<&str>) -> Response {
+        if accepts_json(accept) {
+            return Response::json(self.status, &serde_json::json!({ "error": self.message }));
+        }
+        Response::html(
+            self.status,
+            format!(
+                "<!DOCTYPE html>
+    <html lang=\"en\">
+    <head>
+    <meta charset=\"utf-8\">
+    <title>{status} Error</title>
+    </head>
+    <body>
+    <h1>{status} Error</h1>
+    <p style=\"white-space: pre-wrap\">{message}</p>
+    </body>
+    </html>",
+                status = self.status,
+                message = tera::escape_html(&self.message),
+            ),
+        )
+    }
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.status, self.message)
+    }
+}
+
+impl std::error::Error for HttpError {}
+
+/// Picks the status code that matches why the todo list said no.
+impl From<TodoError> for HttpError {
+    fn from(e: TodoError) -> HttpError {
+        HttpError::new(todo_status(&e), e.to_string())
+    }
+}
+
+impl From<FormError> for HttpError {
+    fn from(e: FormError) -> HttpError {
+        let status = match e {
+            FormError::TooLarge | FormError::TooManyFields => 413,
+            FormError::InvalidUtf8 | FormError::Repeated(_) => 400,
+        };
+        HttpError::new(status, e.to_string())
+    }
+}
+
+pub(crate) fn todo_status(e: &TodoError) -> u16 {
+    match e {
+        TodoError::NotFound(_) => 404,
+        TodoError::InvalidDates | TodoError::EmptyTitle | TodoError::TitleTooLong(_) => 400,
+        TodoError::Conflict(_) => 409,
+        TodoError::Storage(_) => 500,
+    }
+}
+
+/// Reads a todo id from a path. One that isn't a UUID can't name a todo, so it's as missing as
+/// any other.
+pub(crate) fn parse_id(id: &str) -> Result<Uuid, HttpError> {
+    Uuid::parse_str(id).map_err(|_| HttpError::new(404, "bad uuid"))
+}
+
+/// Whether an `Accept` header asks for JSON rather than HTML.
+pub fn accepts_json(accept: Option<&str>) -> bool {
+    let accept = accept.unwrap_or("");
+    accept.contains("application/json") && !accept.contains("text/html")
+}
@@ -0,0 +1,49 @@
+//! Which revision of a todo an edit was based on. The pages and the API both hand out a todo's
+//! revision as its ETag, and take it back in `If-Match` or, failing that, in the edit itself.
+
+use crate::response::HttpError;
+use todo_list::Todo;
+
+pub(crate) fn etag(todo: &Todo) -> String {
+    format!("\"{}\"", todo.revision)
+}
+
+/// Which revision of a todo an edit is meant for.
+pub(crate) enum Expected {
+    Revision(u64),
+    /// `If-Match: *`, for a client that doesn't mind overwriting whatever's there.
+    Any,
+}
+
+impl Expected {
+    /// The revision to hand the todo list along with the edit, given the todo as it is now.
+    pub(crate) fn against(self, current: &Todo) -> u64 {
+        match self {
+            Expected::Revision(revision) => revision,
+            Expected::Any => current.revision,
+        }
+    }
+}
+
+/// The revision an edit was based on, from `If-Match` or failing that what was `sent` with it.
+pub(crate) fn expected_revision(
+    if_match: Option<&str>,
+    sent: Option<u64>,
+) -> Result<Expected, HttpError> {
+    match if_match.map(str::trim) {
+        Some("*") => Ok(Expected::Any),
+        Some(etag) => match etag.trim_start_matches("W/").trim_matches('"').parse() {
+            Ok(revision) => Ok(Expected::Revision(revision)),
+            Err(_) => Err(HttpError::new(
+                400,
+                format!("If-Match should be an ETag like \"3\" or *, not {:?}", etag),
+            )),
+        },
+        None => sent.map(Expected::Revision).ok_or_else(|| {
+            HttpError::new(
+                428,
+                "send the revision being changed in If-Match or the body",
+            )
+        }),
+    }
+}
@@ -0,0 +1,185 @@
+//! The templates pages are rendered with, and in dev mode, reloading them as they're edited.
+
+use crate::assets::Assets;
+use crate::config::Config;
+use crate::response::HttpError;
+use log::{error, info};
+use notify::{RecursiveMode, Watcher};
+use serde::Serialize;
+use std::error::Error;
+use std::path::Path;
+use std::sync::{mpsc, Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+use tera::{Context, Tera};
+
+pub struct Templates {
+    tera: RwLock<Tera>,
+    /// Why the last reload failed. Until the templates are fixed, every page shows this instead.
+    broken: RwLock<Option<String>>,
+    /// Whether to show what went wrong with a template, rather than only that something did.
+    dev: bool,
+}
+
+impl Templates {
+    pub fn new(tera: Tera, dev: bool) -> Templates {
+        Templates {
+            tera: RwLock::new(tera),
+            broken: RwLock::new(None),
+            dev,
+        }
+    }
+
+    /// Loads every template. Template errors are spelled out on the page only with `--dev`, even
+    /// if the templates were read from a directory given on its own.
+    pub fn load(config: &Config, assets: &Assets) -> tera::Result<Templates> {
+        Ok(Templates::new(assets.templates()?, config.dev))
+    }
+
+    /// Loads every template, and keeps reloading the ones read from disk as they change. A server
+    /// can't do anything without its templates, so if they don't parse, this exits.
+    pub fn load_or_exit(config: &Config, assets: &Assets) -> Arc<Templates> {
+        let templates = match Templates::load(config, assets) {
+            Ok(t) => Arc::new(t),
+            Err(e) => {
+                println!("Parsing error(s): {}", e);
+                ::std::process::exit(1);
+            }
+        };
+        if let Some(dir) = assets.templates_dir() {
+            if let Err(e) = templates.watch(dir) {
+                println!("Couldn't watch {} for changes: {}", dir.display(), e);
+            }
+        }
+        templates
+    }
+
+    /// Renders `view` with `template`.
+    pub fn render<V: Serialize>(&self, template: &str, view: &V) -> Result<String, HttpError> {
+        if let Some(broken) = &*self.broken.read().map_err(|_| HttpError::poisoned())? {
+            return Err(self.failure(broken.clone()));
+        }
+        let tera = self.tera.read().map_err(|_| HttpError::poisoned())?;
+        let rendered =
+            Context::from_serialize(view).and_then(|context| tera.render(template, &context));
+        rendered.map_err(|e| {
+            error!("couldn't render {}: {:?}", template, e);
+            self.failure(describe(&e))
+        })
+    }
+
+    /// What a page shows when its template fails. Only dev mode gets to see the details, since
+    /// they give away paths and template source.
+    fn failure(&self, description: String) -> HttpError {
+        if self.dev {
+            HttpError::new(500, description)
+        } else {
+            HttpError::new(500, "template error")
+        }
+    }
+
+    /// Reads the templates from disk again. If they don't parse, the old ones are kept but pages
+    /// show why until the next reload that works.
+    pub fn reload(&self) {
+        let reloaded = match self.tera.read() {
+            Ok(tera) => {
+                let mut tera = tera.clone();
+                tera.full_reload().map(|()| tera)
+            }
+            Err(_) => return,
+        };
+        let broken = match reloaded {
+            Ok(tera) => {
+                info!("reloaded templates");
+                if let Ok(mut current) = self.tera.write() {
+                    *current = tera;
+                }
+                None
+            }
+            Err(e) => {
+                error!("couldn't reload templates: {:?}", e);
+                Some(describe(&e))
+            }
+        };
+        if let Ok(mut current) = self.broken.write() {
+            *current = broken;
+        }
+    }
+
+    /// Reloads the templates whenever something in `dir` changes, for as long as the server runs.
+    pub fn watch(self: &Arc<Templates>, dir: &Path) -> notify::Result<()> {
+        let (changes, changed) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(changes)?;
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        let templates = Arc::clone(self);
+        thread::spawn(move || {
+            let _watcher = watcher;
+            while changed.recv().is_ok() {
+                // Saving a file can take a few events, so wait for them all before reloading.
+                thread::sleep(Duration::from_millis(100));
+                changed.try_iter().for_each(drop);
+                templates.reload();
+            }
+        });
+        Ok(())
+    }
+}
+
+/// A template error along with everything that caused it, which is where Tera says what's wrong.
+fn describe(e: &tera::Error) -> String {
+    let mut description = e.to_string();
+    let mut source = e.source();
+    while let Some(cause) = source {
+        description.push_str(": ");
+        description.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    description
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    /// Templates read from a directory of the test's own holding one good template, so the test
+    /// can break it and reload.
+    fn on_disk(name: &str, dev: bool) -> (PathBuf, Templates) {
+        let dir = std::env::temp_dir()
+            .join(format!("todo-templates-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("page.j2"), "hello {{ name }}").unwrap();
+        let tera = Tera::new(&dir.join("*.j2").to_string_lossy()).unwrap();
+        (dir, Templates::new(tera, dev))
+    }
+
+    fn view() -> serde_json::Value {
+        serde_json::json!({ "name": "world" })
+    }
+
+    #[test]
+    fn a_failed_reload_only_says_so_outside_dev_mode() {
+        let (dir, templates) = on_disk("quiet", false);
+        assert_eq!(templates.render("page.j2", &view()).unwrap(), "hello world");
+        fs::write(dir.join("page.j2"), "hello {{ name").unwrap();
+        templates.reload();
+
+        let error = templates.render("page.j2", &view()).unwrap_err();
+        assert_eq!(error.status, 500);
+        assert_eq!(error.message, "template error");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_failed_reload_is_spelled_out_in_dev_mode() {
+        let (dir, templates) = on_disk("loud", true);
+        fs::write(dir.join("page.j2"), "hello {{ name").unwrap();
+        templates.reload();
+
+        let error = templates.render("page.j2", &view()).unwrap_err();
+        assert!(error.message.contains("page.j2"), "{}", error.message);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
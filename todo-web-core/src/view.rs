@@ -0,0 +1,44 @@
+//! What each template gets to work with.
+
+use chrono::NaiveDate;
+use serde::Serialize;
+use todo_list::{Todo, ValidationErrors};
+
+/// The front page: unarchived todos split into what can be started today and what can't yet.
+#[derive(Serialize)]
+pub struct IndexView {
+    pub today_todos: Vec<Todo>,
+    pub upcoming_todos: Vec<Todo>,
+}
+
+impl IndexView {
+    pub fn new(todos: Vec<Todo>, today: NaiveDate) -> IndexView {
+        let (today_todos, upcoming_todos) = todos
+            .into_iter()
+            .filter(|todo| !todo.archived)
+            .partition(|todo| todo.startable <= today);
+        IndexView {
+            today_todos,
+            upcoming_todos,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ArchivedView {
+    pub archived_todos: Vec<Todo>,
+}
+
+/// The todo form, for creating or updating. `todo` is either a stored `Todo` or a `TodoForm`
+/// being shown back to whoever filled it in.
+#[derive(Serialize)]
+pub struct DetailView<'a, T: Serialize> {
+    pub action: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub todo: Option<&'a T>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub errors: Option<&'a ValidationErrors>,
+    /// On a conflict, the version of the todo the form would overwrite.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current: Option<&'a Todo>,
+}